@@ -0,0 +1,19 @@
+use ld47::map::meta::convert_level;
+use std::{env, process};
+
+// Usage: convert FROM TO
+//
+// Files ending in `.ascii.ron` are read/written as a character grid, others as arrays.
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+
+	if args.len() != 2 {
+		eprintln!("Usage: convert FROM TO");
+		process::exit(2);
+	}
+
+	if let Err(e) = convert_level(&args[0], &args[1]) {
+		eprintln!("{}", e);
+		process::exit(1);
+	}
+}
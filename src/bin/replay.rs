@@ -0,0 +1,72 @@
+use ld47::map::meta::LevelInfo;
+use ld47::mechanics::replay::Replay;
+use ld47::mechanics::simulation::{SimStatus, Simulation};
+use std::{env, process};
+
+// Usage: replay REPLAY...
+//
+// Plays back each replay without a window, and exits with an error unless all of them win.
+fn main() {
+	let paths: Vec<String> = env::args().skip(1).collect();
+
+	if paths.is_empty() {
+		eprintln!("Usage: replay REPLAY...");
+		process::exit(2);
+	}
+
+	let mut all_won = true;
+
+	for path in paths {
+		println!("{}", path);
+
+		let replay = match Replay::read(&path) {
+			Ok(replay) => replay,
+			Err(e) => {
+				println!("\t{}", e);
+				all_won = false;
+				continue;
+			},
+		};
+
+		let level = LevelInfo {
+			name: String::new(),
+			path: replay.level.clone(),
+		};
+
+		let mut sim = match level.get_map().map(Simulation::new) {
+			Ok(Some(sim)) => sim,
+			Ok(None) => {
+				println!("\t{}: no Start tile", replay.level);
+				all_won = false;
+				continue;
+			},
+			Err(e) => {
+				println!("\t{}", e.to_string().replace('\n', "\n\t"));
+				all_won = false;
+				continue;
+			},
+		};
+
+		match replay.play(&mut sim) {
+			SimStatus::Won => {
+				println!("\twon {} in {} turn(s) over {} loop(s)", replay.level, sim.turns_taken(), sim.characters().len());
+			},
+			SimStatus::Stuck => {
+				println!("\tstuck on {} after {} turn(s)", replay.level, sim.turns_taken());
+				all_won = false;
+			},
+			SimStatus::Died => {
+				println!("\tdied on {} after {} turn(s)", replay.level, sim.turns_taken());
+				all_won = false;
+			},
+			SimStatus::Running => {
+				println!("\tran out of commands on {} after {} turn(s)", replay.level, sim.turns_taken());
+				all_won = false;
+			},
+		}
+	}
+
+	if !all_won {
+		process::exit(1);
+	}
+}
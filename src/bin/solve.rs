@@ -0,0 +1,83 @@
+use ld47::map::meta::{Levels, LEVEL_MANIFEST_LOCATION};
+use ld47::mechanics::solver::{Solver, Verdict, DEFAULT_STEP_BUDGET};
+use numerals::roman::Roman;
+use std::{env, process};
+
+// Usage: solve [--budget STEPS] [MANIFEST...]
+fn main() {
+	let mut manifests = vec![];
+	let mut budget = DEFAULT_STEP_BUDGET;
+
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--budget" => {
+				budget = args.next()
+					.and_then(|steps| steps.parse().ok())
+					.expect("--budget needs a number of steps.");
+			},
+			_ => manifests.push(arg),
+		}
+	}
+
+	if manifests.is_empty() {
+		manifests.push(LEVEL_MANIFEST_LOCATION.to_string());
+	}
+
+	let mut all_solved = true;
+
+	for manifest in manifests {
+		let levels = match Levels::from_path(&manifest) {
+			Ok(levels) => levels,
+			Err(e) => {
+				println!("{}", e);
+				all_solved = false;
+				continue;
+			},
+		};
+
+		for (i, level) in levels.data.iter().enumerate() {
+			let romanify: Roman = (i as i16 + 1).into();
+			println!("{:X}: {} ({})", romanify, level.name, level.path);
+
+			let map = match level.get_map() {
+				Ok(map) => map,
+				Err(e) => {
+					println!("\t{}", e.to_string().replace('\n', "\n\t"));
+					all_solved = false;
+					continue;
+				},
+			};
+
+			match Solver::new(map).map(|solver| solver.with_budget(budget).solve()) {
+				Some(Verdict::Solved { solution, minimal }) => {
+					println!(
+						"\tsolved with {} ghost(s), {} turn(s) in the last loop{}",
+						solution.ghosts_used(),
+						solution.final_turns(),
+						if minimal { " (minimal)" } else { "" },
+					);
+					for line in solution.to_string().lines() {
+						println!("\t{}", line);
+					}
+				},
+				Some(Verdict::Unsolvable) => {
+					println!("\tUNSOLVABLE");
+					all_solved = false;
+				},
+				Some(Verdict::Undecided) => {
+					println!("\tno solution found within {} steps", budget);
+					all_solved = false;
+				},
+				None => {
+					println!("\tno Start tile");
+					all_solved = false;
+				},
+			}
+		}
+	}
+
+	if !all_solved {
+		process::exit(1);
+	}
+}
@@ -0,0 +1,577 @@
+use bevy::prelude::*;
+use enum_primitive::*;
+
+use crate::map::meta::{read_map, write_map, LevelFailure, LevelOverride, Levels};
+use crate::map::{
+	ActionChannel,
+	DoorClosing,
+	EntBlueprint,
+	EntData,
+	EntShape,
+	Hazard,
+	Map,
+	TileHeight,
+	TileShape,
+	TileTexture,
+};
+use crate::mechanics::camera::CameraDest;
+use crate::mechanics::controls::Action;
+use crate::mechanics::ender::trigger_restart;
+use crate::mechanics::{
+	Alive,
+	ControlsLocked,
+	Direction,
+	DisplayGridPosition,
+	GhostLimit,
+	GridPosition,
+	Ordinate,
+};
+
+/// Where a level is saved if it was started from scratch, rather than from the manifest.
+const NEW_LEVEL_LOCATION: &str = "assets/levels/new-level.ron";
+const NEW_LEVEL_SIZE: Ordinate = 5;
+
+const EDITOR_LINES: usize = 6;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_resource(Editor::default())
+			.add_startup_system(setup.system())
+			.add_system(editor_toggle.system())
+			.add_system(editor_input.system())
+			.add_system(editor_save.system())
+			.add_system(editor_respawn.system())
+			.add_system(editor_cursor.system())
+			.add_system(editor_text.system());
+	}
+}
+
+/// Working copy of the level being edited.
+///
+/// Every change is pushed into `LevelOverride` and respawned, so leaving the
+/// editor drops straight into a play-test of the edited level.
+#[derive(Debug, Default)]
+pub struct Editor {
+	pub enabled: bool,
+	level: usize,
+	/// The level isn't in the manifest, so it can only be played through `LevelOverride`.
+	outside: bool,
+	path: String,
+	map: Map,
+	/// What's in the level's file, if it could be read.
+	saved: Option<Map>,
+	cursor: GridPosition,
+	channel: usize,
+	/// How long newly placed timed plates stay down; see `hold_turns`.
+	hold: usize,
+	dirty: bool,
+	message: String,
+}
+
+impl Editor {
+	fn open(&mut self, levels: &Levels, level_override: &LevelOverride) {
+		self.level = levels.start_at;
+		self.outside = levels.data.get(self.level).is_none();
+		self.path = levels.data.get(self.level)
+			.map(|info| info.path.as_str())
+			.or_else(|| level_override.path_for(self.level))
+			.unwrap_or(NEW_LEVEL_LOCATION)
+			.to_string();
+
+		self.saved = read_map(&self.path).ok()
+			.map(|map| map.resized(map.width, map.height));
+
+		let edited = level_override.for_level(self.level)
+			.filter(|_| level_override.is_edited(self.level));
+
+		self.map = match (edited, &self.saved) {
+			(Some(map), _) => {
+				self.message = format!("Editing {} (unsaved changes).", self.path);
+				map.clone()
+			},
+			(None, Some(map)) => {
+				self.message = format!("Editing {}.", self.path);
+				map.clone()
+			},
+			(None, None) => {
+				self.message = format!("Could not read {}, starting afresh.", self.path);
+				Map::empty_of_size(NEW_LEVEL_SIZE, NEW_LEVEL_SIZE)
+			},
+		};
+
+		self.cursor = self.cursor.clamp(self.map.width, self.map.height);
+	}
+
+	/// Plays the working copy in place of the level's file, unless they're the same.
+	fn sync(&self, level_override: &mut LevelOverride) {
+		let edited = self.saved.as_ref() != Some(&self.map);
+		let path = Some(self.path.clone())
+			.filter(|_| self.outside);
+
+		*level_override = LevelOverride {
+			index: self.level,
+			map: Some(self.map.clone())
+				.filter(|_| edited || path.is_some()),
+			path,
+			edited,
+		};
+	}
+
+	fn cell(&self) -> usize {
+		self.cursor.unroll(self.map.width) as usize
+	}
+
+	fn ent_index(&self) -> Option<usize> {
+		self.map.ents.as_ref()?
+			.iter()
+			.position(|ent| ent.pos == self.cursor)
+	}
+
+	fn move_cursor(&mut self, dir: Direction) {
+		self.cursor = self.cursor.neighbour(dir)
+			.clamp(self.map.width, self.map.height);
+	}
+
+	fn resize(&mut self, d_width: Ordinate, d_height: Ordinate) {
+		self.map = self.map.resized(self.map.width + d_width, self.map.height + d_height);
+		self.cursor = self.cursor.clamp(self.map.width, self.map.height);
+		self.dirty = true;
+	}
+
+	fn cycle_texture(&mut self, forward: bool) {
+		let i = self.cell();
+		self.map.tiles[i] = cycle(self.map.tiles[i], forward, |v| TileTexture::from_u8(v).is_some());
+		self.dirty = true;
+	}
+
+	fn cycle_shape(&mut self, forward: bool) {
+		let i = self.cell();
+		self.map.tile_shapes[i] = cycle(self.map.tile_shapes[i], forward, |v| TileShape::from_u8(v).is_some());
+		self.dirty = true;
+	}
+
+	fn cycle_rot(&mut self, forward: bool) {
+		let i = self.cell();
+		self.map.tile_rots[i] = cycle(self.map.tile_rots[i], forward, |v| Direction::from_u8(v).is_some());
+		self.dirty = true;
+	}
+
+	fn raise(&mut self, by: isize) {
+		let i = self.cell();
+		let height = TileHeight::from(self.map.heights[i]);
+		let raw = (height.to_raw_height() as isize + by).max(0);
+
+		self.map.heights[i] = match height {
+			TileHeight::Passable(_) => raw,
+			TileHeight::Impassable(_) => -raw,
+		};
+		self.dirty = true;
+	}
+
+	fn toggle_passable(&mut self) {
+		let i = self.cell();
+		if self.map.heights[i] == 0 {
+			self.message = "Tiles at height 0 are always passable.".into();
+		} else {
+			self.map.heights[i] = -self.map.heights[i];
+			self.dirty = true;
+		}
+	}
+
+	fn place(&mut self, data: EntData) {
+		let cursor = self.cursor;
+		let is_start = matches!(data, EntData::Start);
+
+		let ents = self.map.ents.get_or_insert_with(Vec::new);
+		ents.retain(|ent| ent.pos != cursor && !(is_start && matches!(ent.data, EntData::Start)));
+		ents.push(EntBlueprint {
+			pos: cursor,
+			data,
+			rot: None,
+		});
+		self.dirty = true;
+	}
+
+	fn remove_ent(&mut self) {
+		if let Some(i) = self.ent_index() {
+			if let Some(ents) = &mut self.map.ents {
+				ents.remove(i);
+			}
+			self.dirty = true;
+		}
+	}
+
+	fn shift_channel(&mut self, by: isize) {
+		self.channel = (self.channel as isize + by).max(0) as usize;
+
+		if let Some(i) = self.ent_index() {
+			if let Some(ents) = &mut self.map.ents {
+				match &mut ents[i].data {
+					EntData::Button(channel)
+					| EntData::Door(channel)
+					| EntData::HeavyDoor { channel, .. }
+					| EntData::Lever(channel)
+					| EntData::TimedPlate { channel, .. } => {
+						channel.0 = self.channel;
+						self.dirty = true;
+					},
+					_ => {},
+				}
+			}
+		}
+	}
+
+	/// Places a heavy door, or changes what the one already here does when it closes.
+	fn place_heavy_door(&mut self) {
+		if let Some(i) = self.ent_index() {
+			if let Some(ents) = &mut self.map.ents {
+				if let EntData::HeavyDoor { closing, .. } = &mut ents[i].data {
+					*closing = match closing {
+						DoorClosing::Wait => DoorClosing::Push,
+						DoorClosing::Push => DoorClosing::Crush,
+						DoorClosing::Crush => DoorClosing::Wait,
+					};
+					self.dirty = true;
+					return;
+				}
+			}
+		}
+
+		let channel = ActionChannel(self.channel);
+		self.place(EntData::HeavyDoor { channel, closing: DoorClosing::Push });
+	}
+
+	/// Places spikes, or changes the hazard already here.
+	fn place_hazard(&mut self) {
+		let next = match self.ent_index().and_then(|i| self.map.ents.as_ref().map(|ents| &ents[i].data)) {
+			Some(EntData::Hazard(Hazard::Spikes)) => Hazard::Pit,
+			Some(EntData::Hazard(Hazard::Pit)) => Hazard::Lava,
+			_ => Hazard::Spikes,
+		};
+
+		self.place(EntData::Hazard(next));
+	}
+
+	/// Timed plates stay down for at least a turn, or they'd just be buttons.
+	fn hold_turns(&self) -> usize {
+		self.hold.max(1)
+	}
+
+	fn shift_hold(&mut self, by: isize) {
+		self.hold = (self.hold_turns() as isize + by).max(1) as usize;
+
+		if let Some(i) = self.ent_index() {
+			if let Some(ents) = &mut self.map.ents {
+				if let EntData::TimedPlate { turns, .. } = &mut ents[i].data {
+					*turns = self.hold;
+					self.dirty = true;
+				}
+			}
+		}
+	}
+
+	fn shift_turn_limit(&mut self, by: isize) {
+		let limit = &mut self.map.turn_limit.0;
+		*limit = (*limit as isize + by).max(1) as usize;
+		self.dirty = true;
+	}
+
+	fn shift_ghost_limit(&mut self, by: isize) {
+		let limit = self.map.ghost_limit.get_or_insert(GhostLimit(0));
+		limit.0 = (limit.0 as isize + by).max(0) as usize;
+		self.dirty = true;
+	}
+
+	fn lines(&self) -> Vec<String> {
+		let i = self.cell();
+		let map = &self.map;
+
+		let texture = TileTexture::from_u8(map.tiles[i])
+			.map(|t| format!("{:?}", t))
+			.unwrap_or_else(|| "?".into());
+		let shape = TileShape::from_u8(map.tile_shapes[i])
+			.map(|t| format!("{:?}", t))
+			.unwrap_or_else(|| "?".into());
+		let rot = Direction::from_u8(map.tile_rots[i])
+			.map(|t| format!("{:?}", t))
+			.unwrap_or_else(|| "?".into());
+		let height = match TileHeight::from(map.heights[i]) {
+			TileHeight::Passable(h) => format!("height {}", h),
+			TileHeight::Impassable(h) => format!("height {} (impassable)", h),
+		};
+		let ent = match self.ent_index().map(|e| &map.ents.as_ref().unwrap()[e].data) {
+			Some(EntData::Start) => ", start".into(),
+			Some(EntData::End) => ", end".into(),
+			Some(EntData::Button(c)) => format!(", button on {}", c.0),
+			Some(EntData::Door(c)) => format!(", door on {}", c.0),
+			Some(EntData::HeavyDoor { channel, closing }) => format!(", heavy door on {} ({:?} when closing)", channel.0, closing),
+			Some(EntData::Crate) => ", crate".into(),
+			Some(EntData::Hazard(hazard)) => format!(", {:?} hazard", hazard).to_lowercase(),
+			Some(EntData::Lever(c)) => format!(", lever on {}", c.0),
+			Some(EntData::TimedPlate { channel, turns }) => format!(", timed plate on {} for {} turns", channel.0, turns),
+			None => String::new(),
+		};
+
+		let errs = map.validate();
+		let status = match errs.first() {
+			Some(e) if errs.len() > 1 => format!("{} (and {} more problems)", e, errs.len() - 1),
+			Some(e) => e.to_string(),
+			None => "Level is valid.".into(),
+		};
+
+		vec![
+			format!(
+				"EDITING {} ({}x{}), {} turns, {} ghosts, channel {}, plates hold {}",
+				self.path,
+				map.width,
+				map.height,
+				map.turn_limit.0,
+				map.ghost_limit.unwrap_or_default().0,
+				self.channel,
+				self.hold_turns(),
+			),
+			format!("{}: {} {} facing {}, {}{}", self.cursor, texture, shape, rot, height, ent),
+			status,
+			self.message.clone(),
+			"Arrows move, Shift+Arrows resize, T/Y/R texture/shape/rotation, PgUp/PgDn height, I passable".into(),
+			"1-9 start/end/button/door/crate/lever/plate/heavy door/hazard, Del remove, ,/. channel (Shift: plate hold), -/= turns, [/] ghosts, F5 save, F2 play".into(),
+		]
+	}
+}
+
+/// Steps to the next (or previous) value which names a valid variant.
+fn cycle(value: u8, forward: bool, valid: impl Fn(u8) -> bool) -> u8 {
+	let mut next = value;
+	for _ in 0..u8::MAX {
+		next = if forward {
+			next.wrapping_add(1)
+		} else {
+			next.wrapping_sub(1)
+		};
+
+		if valid(next) {
+			return next;
+		}
+	}
+
+	value
+}
+
+pub struct EditorCursor;
+
+#[derive(Debug, Default)]
+pub struct EditorLine(usize);
+
+fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut fonts: ResMut<Assets<Font>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
+
+	for i in 0..EDITOR_LINES {
+		commands.spawn(TextComponents {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: Rect {
+						top: Val::Px(5.0 + 24.0 * i as f32),
+						left: Val::Px(5.0),
+						..Default::default()
+					},
+					..Default::default()
+				},
+				text: Text {
+					value: "".to_string(),
+					font: font_handle,
+					style: TextStyle {
+						font_size: 22.0,
+						color: Color::WHITE,
+					}
+				},
+				..Default::default()
+			})
+			.with(EditorLine(i));
+	}
+
+	let material = materials.add(StandardMaterial {
+		albedo: Color::rgba(1.0, 0.9, 0.2, 0.6),
+		shaded: false,
+		..Default::default()
+	});
+
+	commands.spawn(PbrComponents {
+			mesh: EntShape::BoostSquare.existing_mesh(&mut meshes),
+			material,
+			draw: Draw {
+				is_visible: false,
+				is_transparent: true,
+				..Default::default()
+			},
+			..Default::default()
+		})
+		.with(EditorCursor)
+		.with(DisplayGridPosition(Default::default()));
+}
+
+fn editor_toggle(
+	actions: Res<Input<Action>>,
+	levels: Res<Levels>,
+	level_override: Res<LevelOverride>,
+	mut editor: ResMut<Editor>,
+	mut locked: ResMut<ControlsLocked>,
+	mut failure: ResMut<LevelFailure>,
+) {
+	// Something else (e.g., the menu) already has the controls.
+	if !actions.just_pressed(Action::ToggleEditor) || (locked.0 && !editor.enabled) {
+		return;
+	}
+
+	editor.enabled = !editor.enabled;
+
+	if editor.enabled {
+		editor.open(&levels, &level_override);
+
+		// The working copy replaces whatever failed to load.
+		failure.0 = None;
+	}
+
+	// Either shows the working copy, or starts a fresh play-test of it.
+	editor.dirty = true;
+	locked.0 = editor.enabled;
+}
+
+fn editor_input(
+	input: Res<Input<KeyCode>>,
+	mut editor: ResMut<Editor>,
+) {
+	if !editor.enabled {
+		return;
+	}
+
+	let shift = input.pressed(KeyCode::LShift) || input.pressed(KeyCode::RShift);
+
+	for key in input.get_just_pressed() {
+		match key {
+			KeyCode::Up if shift => editor.resize(1, 0),
+			KeyCode::Down if shift => editor.resize(-1, 0),
+			KeyCode::Right if shift => editor.resize(0, 1),
+			KeyCode::Left if shift => editor.resize(0, -1),
+			KeyCode::Up => editor.move_cursor(Direction::North),
+			KeyCode::Down => editor.move_cursor(Direction::South),
+			KeyCode::Right => editor.move_cursor(Direction::East),
+			KeyCode::Left => editor.move_cursor(Direction::West),
+			KeyCode::T => editor.cycle_texture(!shift),
+			KeyCode::Y => editor.cycle_shape(!shift),
+			KeyCode::R => editor.cycle_rot(!shift),
+			KeyCode::PageUp => editor.raise(1),
+			KeyCode::PageDown => editor.raise(-1),
+			KeyCode::I => editor.toggle_passable(),
+			KeyCode::Key1 => editor.place(EntData::Start),
+			KeyCode::Key2 => editor.place(EntData::End),
+			KeyCode::Key3 => {
+				let channel = ActionChannel(editor.channel);
+				editor.place(EntData::Button(channel));
+			},
+			KeyCode::Key4 => {
+				let channel = ActionChannel(editor.channel);
+				editor.place(EntData::Door(channel));
+			},
+			KeyCode::Key5 => editor.place(EntData::Crate),
+			KeyCode::Key6 => {
+				let channel = ActionChannel(editor.channel);
+				editor.place(EntData::Lever(channel));
+			},
+			KeyCode::Key7 => {
+				let channel = ActionChannel(editor.channel);
+				let turns = editor.hold_turns();
+				editor.place(EntData::TimedPlate { channel, turns });
+			},
+			KeyCode::Key8 => editor.place_heavy_door(),
+			KeyCode::Key9 => editor.place_hazard(),
+			KeyCode::Delete => editor.remove_ent(),
+			KeyCode::Comma if shift => editor.shift_hold(-1),
+			KeyCode::Period if shift => editor.shift_hold(1),
+			KeyCode::Comma => editor.shift_channel(-1),
+			KeyCode::Period => editor.shift_channel(1),
+			KeyCode::Minus => editor.shift_turn_limit(-1),
+			KeyCode::Equals => editor.shift_turn_limit(1),
+			KeyCode::LBracket => editor.shift_ghost_limit(-1),
+			KeyCode::RBracket => editor.shift_ghost_limit(1),
+			_ => {},
+		}
+	}
+}
+
+fn editor_save(
+	input: Res<Input<KeyCode>>,
+	mut editor: ResMut<Editor>,
+	mut level_override: ResMut<LevelOverride>,
+) {
+	if !editor.enabled || !input.just_pressed(KeyCode::F5) {
+		return;
+	}
+
+	let message = match write_map(&editor.map, &editor.path) {
+		Ok(()) => {
+			// The file now matches, so it's played from there again.
+			editor.saved = Some(editor.map.clone());
+			editor.sync(&mut level_override);
+			format!("Saved to {}.", editor.path)
+		},
+		Err(e) => e.to_string(),
+	};
+
+	editor.message = message;
+}
+
+fn editor_respawn(
+	mut editor: ResMut<Editor>,
+	mut level_override: ResMut<LevelOverride>,
+	mut ents_query: Query<&mut Alive>,
+) {
+	if editor.dirty {
+		editor.dirty = false;
+		editor.sync(&mut level_override);
+		trigger_restart(&mut ents_query);
+	}
+}
+
+fn editor_cursor(
+	editor: Res<Editor>,
+	mut dest: ResMut<CameraDest>,
+	mut query: Query<(&EditorCursor, &mut DisplayGridPosition, &mut Draw)>,
+) {
+	for (_tag, mut pos, mut draw) in &mut query.iter() {
+		draw.is_visible = editor.enabled;
+		pos.0 = editor.cursor;
+	}
+
+	if editor.enabled {
+		dest.0 = Some(editor.cursor);
+	}
+}
+
+fn editor_text(
+	editor: Res<Editor>,
+	mut query: Query<(&EditorLine, &mut Text)>,
+) {
+	let lines = if editor.enabled {
+		editor.lines()
+	} else {
+		vec![]
+	};
+
+	for (line, mut text) in &mut query.iter() {
+		let value = lines.get(line.0)
+			.cloned()
+			.unwrap_or_default();
+
+		if text.value != value {
+			text.value = value;
+		}
+	}
+}
@@ -0,0 +1,4 @@
+pub mod editor;
+pub mod map;
+pub mod mechanics;
+pub mod ui;
@@ -1,19 +1,23 @@
-mod map;
-mod mechanics;
-mod ui;
-
 use bevy::ecs::WorldWriter;
 use bevy::{
 	prelude::*,
 	render::pass::ClearColor,
 };
-use map::{EntShape, MapPlugin};
-use mechanics::{
+use ld47::{map, mechanics};
+use ld47::editor::EditorPlugin;
+use ld47::map::{EntShape, MapPlugin};
+use ld47::map::meta::{LevelInfo, LevelOverride, Levels, ManifestPath};
+use ld47::mechanics::{
 	character::{ActiveCharacter, Character},
+	controls::{InputMap, CONTROLS_LOCATION},
+	events::DebugKeys,
+	replay::{ActiveReplay, Replay, ReplayPlayer, DEFAULT_REPLAY_SPEED},
 	MechanicsPlugin,
 	TurnLimit,
 };
-use ui::UiPlugin;
+use ld47::ui::UiPlugin;
+use ld47::ui::menu::Menu;
+use std::{env, process};
 
 fn hello_world(time: Res<Time>, mut timer: ResMut<TestTtime>) {
 	timer.0.tick(time.delta_seconds);
@@ -34,28 +38,253 @@ impl Plugin for HelloPlugin {
 	}
 }
 
+const USAGE: &str = "\
+Usage: ld47 [OPTIONS]
+
+Options:
+	--manifest PATH       Read levels from PATH rather than assets/levels.ron.
+	--level N|NAME        Start at level N (counting from 1) or the level called NAME.
+	--map PATH            Play a single level file, outside of any manifest.
+	--controls PATH       Read key bindings from PATH rather than assets/controls.ron.
+	--debug               Enable the debug actions, which fire level start, restart, and exit events.
+	--check               Validate every level (or just --map) without opening a window.
+	--replay PATH         Play back a replay file.
+	--replay-speed TURNS  Replay at TURNS turns per second (default 4).
+	--help                Show this message.";
+
+#[derive(Debug)]
+struct Options {
+	manifest: Option<String>,
+	level: Option<String>,
+	map: Option<String>,
+	debug: bool,
+	check: bool,
+	replay: Option<String>,
+	replay_speed: Option<f32>,
+	controls: String,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			manifest: None,
+			level: None,
+			map: None,
+			debug: false,
+			check: false,
+			replay: None,
+			replay_speed: None,
+			controls: CONTROLS_LOCATION.to_string(),
+		}
+	}
+}
+
+impl Options {
+	fn from_args() -> Self {
+		let mut out = Self::default();
+
+		let mut args = env::args().skip(1);
+		while let Some(arg) = args.next() {
+			let mut value = |name: &str| args.next().unwrap_or_else(|| {
+				eprintln!("{} needs a value.\n\n{}", name, USAGE);
+				process::exit(2);
+			});
+
+			match arg.as_str() {
+				"--manifest" => {
+					out.manifest = Some(value("--manifest"));
+				},
+				"--level" => {
+					out.level = Some(value("--level"));
+				},
+				"--map" => {
+					out.map = Some(value("--map"));
+				},
+				"--controls" => {
+					out.controls = value("--controls");
+				},
+				"--debug" => {
+					out.debug = true;
+				},
+				"--check" => {
+					out.check = true;
+				},
+				"--replay" => {
+					out.replay = Some(value("--replay"));
+				},
+				"--replay-speed" => {
+					out.replay_speed = Some(value("--replay-speed").parse().unwrap_or_else(|_| {
+						eprintln!("--replay-speed needs a number of turns per second.");
+						process::exit(2);
+					}));
+				},
+				"--help" => {
+					println!("{}", USAGE);
+					process::exit(0);
+				},
+				_ => {
+					eprintln!("Unknown argument: {}\n\n{}", arg, USAGE);
+					process::exit(2);
+				},
+			}
+		}
+
+		out
+	}
+}
+
 fn main() {
 	// map::meta::Levels::generate_example();
 	// map::Map::generate_example_of_size(4, 3);
 
-	App::build()
+	let options = Options::from_args();
+
+	let manifest_path = options.manifest.clone()
+		.map(ManifestPath)
+		.unwrap_or_default();
+
+	if options.check {
+		process::exit(if check_levels(&manifest_path, options.map.as_deref()) { 0 } else { 1 });
+	}
+
+	let (mut levels, failure) = match Levels::from_path(&manifest_path.0) {
+		Ok(levels) => (levels, None),
+		Err(e) => (Default::default(), Some(e)),
+	};
+
+	let save = map::save::SaveData::load();
+	if !levels.data.is_empty() && manifest_path.is_default() {
+		levels.start_at = save.current.min(levels.data.len());
+	}
+
+	if let Some(level) = &options.level {
+		levels.start_at = find_level(&levels, level).unwrap_or_else(|| {
+			eprintln!("No level {} in {}.", level, manifest_path.0);
+			process::exit(2);
+		});
+	}
+
+	// Both of these skip the title screen, and jump straight into a level.
+	let mut level_override = LevelOverride::default();
+	let mut replay = ActiveReplay::default();
+	let skip_menu = options.map.is_some() || options.replay.is_some() || options.level.is_some();
+
+	if let Some(path) = &options.map {
+		play_outside_manifest(path, &mut levels, &mut level_override);
+	}
+
+	if let Some(path) = &options.replay {
+		let speed = options.replay_speed.unwrap_or(DEFAULT_REPLAY_SPEED);
+		replay.0 = Some(start_replay(path, speed, &mut levels, &mut level_override));
+	}
+
+	let mut app = App::build();
+
+	app
 		.add_resource(WindowDescriptor {
 			title: "LD47: Multitasking".to_string(),
 			..Default::default()
 		})
 		.add_resource(ClearColor(Color::hex("341c27").expect("Ha")))
 		.add_default_plugins()
-		.add_resource(map::meta::Levels::get_self())
+		.add_resource(levels)
+		.add_resource(manifest_path)
+		.add_resource(map::meta::LevelFailure(failure))
+		.add_resource(save)
 		// .add_plugin(BillboardPlugin)
 		// .add_resource(Msaa { samples: 4 })
 		.add_plugin(HelloPlugin)
 		.add_plugin(UiPlugin)
 		.add_plugin(MapPlugin)
 		.add_plugin(MechanicsPlugin)
+		.add_plugin(EditorPlugin)
 		.add_system(hello_world.system())
 		.add_startup_system(setup.system())
 		// .add_system(world_saver.system())
-		.run();
+		// These replace the defaults set up by the plugins above.
+		.add_resource(level_override)
+		.add_resource(replay)
+		.add_resource(InputMap::load(&options.controls))
+		.add_resource(DebugKeys(options.debug));
+
+	if skip_menu {
+		app.add_resource(Menu::closed());
+	}
+
+	app.run();
+}
+
+/// Looks up a level by its number (as shown in game, counting from 1) or its name.
+fn find_level(levels: &Levels, level: &str) -> Option<usize> {
+	match level.parse::<usize>() {
+		Ok(n) if n >= 1 && n <= levels.data.len() => Some(n - 1),
+		Ok(_) => None,
+		Err(_) => levels.data.iter()
+			.position(|info| info.name.eq_ignore_ascii_case(level)),
+	}
+}
+
+/// Plays a level straight from its file, in the slot just past the end of the manifest.
+fn play_outside_manifest(path: &str, levels: &mut Levels, level_override: &mut LevelOverride) {
+	let map = LevelInfo { name: String::new(), path: path.to_string() }
+		.get_map()
+		.unwrap_or_else(|e| {
+			eprintln!("{}", e);
+			process::exit(1);
+		});
+
+	levels.start_at = levels.data.len();
+	*level_override = LevelOverride::outside(levels.start_at, map, path);
+}
+
+/// Points `levels` at the replay's level, loading it from its file if it isn't in the manifest.
+fn start_replay(path: &str, speed: f32, levels: &mut Levels, level_override: &mut LevelOverride) -> ReplayPlayer {
+	let replay = Replay::read(path).unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		process::exit(1);
+	});
+
+	match levels.data.iter().position(|level| level.path == replay.level) {
+		Some(i) => {
+			levels.start_at = i;
+		},
+		None => {
+			play_outside_manifest(&replay.level, levels, level_override);
+		},
+	}
+
+	ReplayPlayer::new(&replay, speed)
+}
+
+/// Prints any problems with the given level, or every level in the manifest.
+///
+/// Returns whether everything was valid.
+fn check_levels(manifest_path: &ManifestPath, map: Option<&str>) -> bool {
+	let levels = match map {
+		Some(path) => vec![LevelInfo { name: path.to_string(), path: path.to_string() }],
+		None => match Levels::from_path(&manifest_path.0) {
+			Ok(levels) => levels.data,
+			Err(e) => {
+				println!("{}", e);
+				return false;
+			},
+		},
+	};
+
+	let mut all_ok = true;
+	for level in levels {
+		match level.get_map() {
+			Ok(_) => {
+				println!("{} ({}): ok", level.name, level.path);
+			},
+			Err(e) => {
+				println!("{} ({}):\n\t{}", level.name, level.path, e.to_string().replace('\n', "\n\t"));
+				all_ok = false;
+			},
+		}
+	}
+
+	all_ok
 }
 
 fn setup(
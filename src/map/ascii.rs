@@ -0,0 +1,411 @@
+use crate::mechanics::{
+	Direction,
+	GhostLimit,
+	GridPosition,
+	Ordinate,
+	TurnLimit,
+};
+
+use super::{
+	ActionChannel,
+	ChannelLogic,
+	DoorClosing,
+	EntBlueprint,
+	EntData,
+	Hazard,
+	Map,
+	Wall,
+};
+
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt,
+};
+
+const LEGEND_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Everything about a single cell of terrain.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct TileKind {
+	/// Height (and passability) of the tile.
+	pub height: isize,
+	/// Tile texture/type data.
+	pub texture: u8,
+	#[serde(default)]
+	pub shape: u8,
+	#[serde(default)]
+	pub rot: u8,
+}
+
+/// A level laid out as a grid of legend characters, rather than parallel arrays.
+///
+/// Each string in `grid` is one row (`y`), holding one whitespace-separated cell per
+/// column (`x`): the same order as the arrays in `Map`. So, moving `North` goes
+/// one cell right, and `East` goes one row down.
+///
+/// A cell is a legend character, optionally followed by an entity (`S`, `E`, `B0`,
+/// `D0`, `D0p`, `C`, `L0`, `T0/3`, `^`, ...) and a rotation (`@N`, `@E`, `@S`, `@W`), e.g. `aB1@W`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AsciiMap {
+	pub legend: BTreeMap<char, TileKind>,
+
+	pub grid: Vec<String>,
+
+	#[serde(default)]
+	pub walls: Option<Vec<Wall>>,
+
+	#[serde(default)]
+	pub channels: Option<Vec<ChannelLogic>>,
+
+	pub turn_limit: TurnLimit,
+
+	#[serde(default)]
+	pub ghost_limit: Option<GhostLimit>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsciiError {
+	EmptyGrid,
+	/// A row has a different number of cells from the first row.
+	RaggedRow {
+		row: usize,
+		expected: usize,
+		found: usize,
+	},
+	UnknownLegend {
+		pos: GridPosition,
+		symbol: char,
+	},
+	BadEntity {
+		pos: GridPosition,
+		token: String,
+	},
+	/// The grid format only has room for one entity per cell.
+	SharedCell {
+		pos: GridPosition,
+	},
+	/// The grid format has no cell to put this entity in.
+	EntOutOfBounds {
+		pos: GridPosition,
+	},
+	TooManyTileKinds(usize),
+}
+
+impl fmt::Display for AsciiError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		use AsciiError::*;
+		match self {
+			EmptyGrid =>
+				write!(f, "grid has no cells"),
+			RaggedRow { row, expected, found } =>
+				write!(f, "row {} has {} cells, expected {}", row, found, expected),
+			UnknownLegend { pos, symbol } =>
+				write!(f, "'{}' at {} is not in the legend", symbol, pos),
+			BadEntity { pos, token } =>
+				write!(f, "'{}' at {} is not an entity", token, pos),
+			SharedCell { pos } =>
+				write!(f, "more than one entity at {}", pos),
+			EntOutOfBounds { pos } =>
+				write!(f, "entity at {} is outside the map", pos),
+			TooManyTileKinds(count) =>
+				write!(f, "{} kinds of tile is more than the legend can hold", count),
+		}
+	}
+}
+
+fn direction_char(direction: Direction) -> char {
+	match direction {
+		Direction::North => 'N',
+		Direction::East => 'E',
+		Direction::South => 'S',
+		Direction::West => 'W',
+	}
+}
+
+fn parse_ent(token: &str) -> Option<EntBlueprint> {
+	let mut parts = token.splitn(2, '@');
+	let ent = parts.next()?;
+
+	let rot = match parts.next() {
+		None => None,
+		Some("N") => Some(Direction::North),
+		Some("E") => Some(Direction::East),
+		Some("S") => Some(Direction::South),
+		Some("W") => Some(Direction::West),
+		Some(_) => return None,
+	};
+
+	let channel = || ent.get(1..)
+		.and_then(|c| c.parse().ok())
+		.map(ActionChannel);
+
+	let data = match ent.chars().next()? {
+		'S' if ent.len() == 1 => EntData::Start,
+		'E' if ent.len() == 1 => EntData::End,
+		'B' => EntData::Button(channel()?),
+		'D' => {
+			let rest = ent.get(1..)?;
+			let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+			let channel = ActionChannel(rest[..digits].parse().ok()?);
+
+			match &rest[digits..] {
+				"" => EntData::Door(channel),
+				"w" => EntData::HeavyDoor { channel, closing: DoorClosing::Wait },
+				"p" => EntData::HeavyDoor { channel, closing: DoorClosing::Push },
+				"c" => EntData::HeavyDoor { channel, closing: DoorClosing::Crush },
+				_ => return None,
+			}
+		},
+		'C' if ent.len() == 1 => EntData::Crate,
+		'^' if ent.len() == 1 => EntData::Hazard(Hazard::Spikes),
+		'O' if ent.len() == 1 => EntData::Hazard(Hazard::Pit),
+		'~' if ent.len() == 1 => EntData::Hazard(Hazard::Lava),
+		'L' => EntData::Lever(channel()?),
+		'T' => {
+			let mut parts = ent.get(1..)?.splitn(2, '/');
+			let channel = ActionChannel(parts.next()?.parse().ok()?);
+			let turns = parts.next()?.parse().ok()?;
+			EntData::TimedPlate { channel, turns }
+		},
+		_ => return None,
+	};
+
+	Some(EntBlueprint {
+		pos: Default::default(),
+		data,
+		rot,
+	})
+}
+
+fn ent_token(blueprint: &EntBlueprint) -> String {
+	let mut out = match &blueprint.data {
+		EntData::Start => "S".to_string(),
+		EntData::End => "E".to_string(),
+		EntData::Button(channel) => format!("B{}", channel.0),
+		EntData::Door(channel) => format!("D{}", channel.0),
+		EntData::HeavyDoor { channel, closing } => format!("D{}{}", channel.0, match closing {
+			DoorClosing::Wait => 'w',
+			DoorClosing::Push => 'p',
+			DoorClosing::Crush => 'c',
+		}),
+		EntData::Crate => "C".to_string(),
+		EntData::Hazard(Hazard::Spikes) => "^".to_string(),
+		EntData::Hazard(Hazard::Pit) => "O".to_string(),
+		EntData::Hazard(Hazard::Lava) => "~".to_string(),
+		EntData::Lever(channel) => format!("L{}", channel.0),
+		EntData::TimedPlate { channel, turns } => format!("T{}/{}", channel.0, turns),
+	};
+
+	if let Some(rot) = blueprint.rot {
+		out.push('@');
+		out.push(direction_char(rot));
+	}
+
+	out
+}
+
+impl AsciiMap {
+	pub fn into_map(self) -> Result<Map, AsciiError> {
+		let rows: Vec<Vec<&str>> = self.grid.iter()
+			.map(|row| row.split_whitespace().collect())
+			.collect();
+
+		let width = rows.first()
+			.map(Vec::len)
+			.unwrap_or_default();
+
+		if width == 0 {
+			return Err(AsciiError::EmptyGrid);
+		}
+
+		for (row, cells) in rows.iter().enumerate() {
+			if cells.len() != width {
+				return Err(AsciiError::RaggedRow {
+					row,
+					expected: width,
+					found: cells.len(),
+				});
+			}
+		}
+
+		let mut map = Map::empty_of_size(width as Ordinate, rows.len() as Ordinate);
+		let mut ents = vec![];
+
+		for (y, cells) in rows.iter().enumerate() {
+			for (x, cell) in cells.iter().enumerate() {
+				let pos = GridPosition {
+					x: x as Ordinate,
+					y: y as Ordinate,
+				};
+				let i = pos.unroll(map.width) as usize;
+
+				let mut chars = cell.chars();
+				let symbol = chars.next().unwrap_or_default();
+				let kind = self.legend.get(&symbol)
+					.ok_or(AsciiError::UnknownLegend { pos, symbol })?;
+
+				map.tiles[i] = kind.texture;
+				map.tile_shapes[i] = kind.shape;
+				map.tile_rots[i] = kind.rot;
+				map.heights[i] = kind.height;
+
+				let rest = chars.as_str();
+				if !rest.is_empty() {
+					let mut blueprint = parse_ent(rest)
+						.ok_or_else(|| AsciiError::BadEntity { pos, token: rest.to_string() })?;
+					blueprint.pos = pos;
+					ents.push(blueprint);
+				}
+			}
+		}
+
+		map.ents = Some(ents);
+		map.walls = self.walls;
+		map.channels = self.channels;
+		map.turn_limit = self.turn_limit;
+		map.ghost_limit = self.ghost_limit;
+
+		Ok(map)
+	}
+
+	pub fn from_map(map: &Map) -> Result<Self, AsciiError> {
+		if map.width <= 0 || map.height <= 0 {
+			return Err(AsciiError::EmptyGrid);
+		}
+
+		let mut kinds = vec![];
+		for i in 0..map.len() {
+			kinds.push(TileKind {
+				height: map.heights.get(i).copied().unwrap_or_default(),
+				texture: map.tiles.get(i).copied().unwrap_or_default(),
+				shape: map.tile_shapes.get(i).copied().unwrap_or_default(),
+				rot: map.tile_rots.get(i).copied().unwrap_or_default(),
+			});
+		}
+
+		let mut unique = kinds.clone();
+		unique.sort();
+		unique.dedup();
+
+		if unique.len() > LEGEND_CHARS.len() {
+			return Err(AsciiError::TooManyTileKinds(unique.len()));
+		}
+
+		let symbols: HashMap<TileKind, char> = unique.iter()
+			.copied()
+			.zip(LEGEND_CHARS.chars())
+			.collect();
+
+		let mut cells: Vec<String> = kinds.iter()
+			.map(|kind| symbols[kind].to_string())
+			.collect();
+		let mut has_ent = vec![false; map.len()];
+
+		for blueprint in map.ents.iter().flatten() {
+			let pos = blueprint.pos;
+			if !map.in_bounds(pos) {
+				return Err(AsciiError::EntOutOfBounds { pos });
+			}
+
+			let i = pos.unroll(map.width) as usize;
+			if has_ent[i] {
+				return Err(AsciiError::SharedCell { pos });
+			}
+
+			has_ent[i] = true;
+			cells[i].push_str(&ent_token(blueprint));
+		}
+
+		let cell_width = cells.iter()
+			.map(String::len)
+			.max()
+			.unwrap_or_default();
+
+		let grid = cells.chunks(map.width as usize)
+			.map(|row| row.iter()
+				.map(|cell| format!("{:<1$}", cell, cell_width))
+				.collect::<Vec<_>>()
+				.join(" ")
+				.trim_end()
+				.to_string())
+			.collect();
+
+		Ok(Self {
+			legend: symbols.into_iter()
+				.map(|(kind, symbol)| (symbol, kind))
+				.collect(),
+			grid,
+			walls: map.walls.clone(),
+			channels: map.channels.clone(),
+			turn_limit: map.turn_limit,
+			ghost_limit: map.ghost_limit,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::meta::{Levels, LEVEL_MANIFEST_LOCATION};
+
+	/// Treats a map without entities the same as one with an empty list of them, and
+	/// lists entities in grid order, the only order a grid can give them.
+	fn normalised(mut map: Map) -> Map {
+		map.ents = map.ents.filter(|ents| !ents.is_empty());
+		for ents in map.ents.iter_mut() {
+			ents.sort_by_key(|blueprint| (blueprint.pos.y, blueprint.pos.x));
+		}
+		map
+	}
+
+	#[test]
+	fn every_level_in_the_manifest_survives_a_round_trip() {
+		let levels = Levels::from_path(LEVEL_MANIFEST_LOCATION)
+			.unwrap_or_else(|e| panic!("{}", e));
+
+		for level in levels.data.iter() {
+			let map = level.get_map()
+				.unwrap_or_else(|e| panic!("{}", e));
+
+			let ascii = AsciiMap::from_map(&map)
+				.unwrap_or_else(|e| panic!("{}: {}", level.path, e));
+			let back = ascii.into_map()
+				.unwrap_or_else(|e| panic!("{}: {}", level.path, e));
+			assert!(normalised(map) == normalised(back), "{} changed in a round trip", level.path);
+		}
+	}
+
+	#[test]
+	fn every_entity_has_a_token() {
+		let cases = vec![
+			("S", EntData::Start),
+			("E", EntData::End),
+			("B3", EntData::Button(ActionChannel(3))),
+			("D3", EntData::Door(ActionChannel(3))),
+			("D3w", EntData::HeavyDoor { channel: ActionChannel(3), closing: DoorClosing::Wait }),
+			("D3p", EntData::HeavyDoor { channel: ActionChannel(3), closing: DoorClosing::Push }),
+			("D3c", EntData::HeavyDoor { channel: ActionChannel(3), closing: DoorClosing::Crush }),
+			("C", EntData::Crate),
+			("^", EntData::Hazard(Hazard::Spikes)),
+			("O", EntData::Hazard(Hazard::Pit)),
+			("~", EntData::Hazard(Hazard::Lava)),
+			("L3", EntData::Lever(ActionChannel(3))),
+			("T3/2", EntData::TimedPlate { channel: ActionChannel(3), turns: 2 }),
+		];
+
+		for (token, data) in cases {
+			for &(suffix, rot) in [("", None), ("@W", Some(Direction::West))].iter() {
+				let blueprint = EntBlueprint {
+					pos: Default::default(),
+					data: data.clone(),
+					rot,
+				};
+				let token = format!("{}{}", token, suffix);
+
+				assert_eq!(ent_token(&blueprint), token);
+				assert_eq!(parse_ent(&token), Some(blueprint));
+			}
+		}
+	}
+}
@@ -1,6 +1,8 @@
 use crate::mechanics::Ordinate;
 
 use super::Map;
+use super::ascii::{AsciiError, AsciiMap};
+use super::validation::MapError;
 
 use ron::{
 	de::from_reader,
@@ -8,15 +10,127 @@ use ron::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
+	fmt,
 	fs::File,
-	io::Write,
+	io::{self, Write},
 };
 
-const LEVEL_MANIFEST_LOCATION: &str = "assets/levels.ron";
+pub const LEVEL_MANIFEST_LOCATION: &str = "assets/levels.ron";
 const EXAMPLE_MANIFEST_LOCATION: &str = "assets/levels.ron-ex";
 
 const EXAMPLE_LEVEL_LOCATION: &str = "assets/levels/test-level.ron";
 
+/// Level files ending with this are read as an `AsciiMap`, rather than a `Map`.
+pub const ASCII_LEVEL_EXTENSION: &str = ".ascii.ron";
+
+/// Why a manifest or level file couldn't be used.
+#[derive(Debug)]
+pub enum LevelError {
+	Io(String, io::Error),
+	Parse(String, ron::de::Error),
+	Ascii(String, AsciiError),
+	Invalid(String, Vec<MapError>),
+}
+
+impl LevelError {
+	pub fn path(&self) -> &str {
+		match self {
+			LevelError::Io(path, _) => path,
+			LevelError::Parse(path, _) => path,
+			LevelError::Ascii(path, _) => path,
+			LevelError::Invalid(path, _) => path,
+		}
+	}
+
+	/// One line per problem, suitable for showing to the player.
+	pub fn details(&self) -> Vec<String> {
+		match self {
+			LevelError::Io(_, e) => vec![format!("could not be read: {}", e)],
+			LevelError::Parse(_, e) => vec![format!("could not be parsed: {}", e)],
+			LevelError::Ascii(_, e) => vec![format!("could not be converted: {}", e)],
+			LevelError::Invalid(_, errs) => errs.iter()
+				.map(MapError::to_string)
+				.collect(),
+		}
+	}
+}
+
+impl fmt::Display for LevelError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:", self.path())?;
+		for line in self.details() {
+			write!(f, "\n\t{}", line)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// The most recent failure to load the manifest or current level, shown in place of the map.
+#[derive(Debug, Default)]
+pub struct LevelFailure(pub Option<LevelError>);
+
+/// Where the manifest in play was read from.
+#[derive(Clone, Debug)]
+pub struct ManifestPath(pub String);
+
+impl Default for ManifestPath {
+	fn default() -> Self {
+		Self(LEVEL_MANIFEST_LOCATION.to_string())
+	}
+}
+
+impl ManifestPath {
+	/// Saved progress (`SaveData::current`) only indexes into the game's own manifest.
+	pub fn is_default(&self) -> bool {
+		self.0 == LEVEL_MANIFEST_LOCATION
+	}
+}
+
+/// A level played from memory, rather than from its file in the manifest.
+///
+/// This is either an edited copy of a level, or one from outside the manifest
+/// (e.g., with `--map`), which sits at the index just past its end.
+#[derive(Debug, Default)]
+pub struct LevelOverride {
+	/// Manifest index of the level this replaces.
+	pub index: usize,
+	pub map: Option<Map>,
+	/// The file a level from outside the manifest was read from, so that it can be reloaded.
+	pub path: Option<String>,
+	/// `map` has changes which aren't saved in any file.
+	pub edited: bool,
+}
+
+impl LevelOverride {
+	/// Plays a level from outside the manifest, straight from its file.
+	pub fn outside(index: usize, map: Map, path: &str) -> Self {
+		Self {
+			index,
+			map: Some(map),
+			path: Some(path.to_string()),
+			edited: false,
+		}
+	}
+
+	/// The map to play, if it's for the level at `index`.
+	pub fn for_level(&self, index: usize) -> Option<&Map> {
+		self.map.as_ref()
+			.filter(|_| self.index == index)
+	}
+
+	/// Where the level at `index` was read from, if it's outside the manifest.
+	pub fn path_for(&self, index: usize) -> Option<&str> {
+		self.path.as_deref()
+			.filter(|_| self.index == index)
+	}
+
+	/// Whether the level at `index` is being played with unsaved changes.
+	pub fn is_edited(&self, index: usize) -> bool {
+		self.edited && self.for_level(index).is_some()
+	}
+}
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Levels {
 	pub data: Vec<LevelInfo>,
@@ -24,12 +138,16 @@ pub struct Levels {
 }
 
 impl Levels {
-	pub fn get_self() -> Self {
-		let f = File::open(LEVEL_MANIFEST_LOCATION)
-			.expect("Level load failed.");
+	pub fn get_self() -> Result<Self, LevelError> {
+		Self::from_path(LEVEL_MANIFEST_LOCATION)
+	}
+
+	pub fn from_path(path: &str) -> Result<Self, LevelError> {
+		let f = File::open(path)
+			.map_err(|e| LevelError::Io(path.to_string(), e))?;
 
 		from_reader(f)
-			.expect("Apparently misread.")
+			.map_err(|e| LevelError::Parse(path.to_string(), e))
 	}
 
 	pub fn generate_example() {
@@ -52,7 +170,7 @@ impl Levels {
 		// self.load_current()
 	}
 
-	pub fn load_current(&self) -> Map {
+	pub fn load_current(&self) -> Result<Map, LevelError> {
 		self.data[self.start_at].get_map()
 	}
 }
@@ -64,12 +182,101 @@ pub struct LevelInfo {
 }
 
 impl LevelInfo {
-	pub fn get_map(&self) -> Map {
-		let f = File::open(&self.path).expect("Level load failed.");
+	/// Reads and validates this level's map.
+	pub fn get_map(&self) -> Result<Map, LevelError> {
+		let map = read_map(&self.path)?;
 
+		let errs = map.validate();
+		if errs.is_empty() {
+			Ok(map)
+		} else {
+			Err(LevelError::Invalid(self.path.clone(), errs))
+		}
+	}
+}
+
+/// Reads a level in either format, depending on its extension.
+pub fn read_map(path: &str) -> Result<Map, LevelError> {
+	let f = File::open(path)
+		.map_err(|e| LevelError::Io(path.to_string(), e))?;
+
+	if path.ends_with(ASCII_LEVEL_EXTENSION) {
+		let ascii: AsciiMap = from_reader(f)
+			.map_err(|e| LevelError::Parse(path.to_string(), e))?;
+
+		ascii.into_map()
+			.map_err(|e| LevelError::Ascii(path.to_string(), e))
+	} else {
 		from_reader(f)
-			.expect("Apparently misread.")
+			.map_err(|e| LevelError::Parse(path.to_string(), e))
+	}
+}
+
+/// Writes a level in either format, depending on its extension.
+pub fn write_map(map: &Map, path: &str) -> Result<(), LevelError> {
+	let out = if path.ends_with(ASCII_LEVEL_EXTENSION) {
+		let ascii = AsciiMap::from_map(map)
+			.map_err(|e| LevelError::Ascii(path.to_string(), e))?;
+
+		to_string_pretty(&ascii, PrettyConfig::new()).expect("Must ser")
+	} else {
+		map_to_ron(map)
+	};
+
+	File::create(path)
+		.and_then(|mut f| f.write_all(out.as_bytes()))
+		.map_err(|e| LevelError::Io(path.to_string(), e))
+}
+
+/// Converts a level file between formats, e.g. `intro.ron` to `intro.ascii.ron`.
+pub fn convert_level(from: &str, to: &str) -> Result<(), LevelError> {
+	write_map(&read_map(from)?, to)
+}
+
+/// Lays out each per-tile array as a grid, in the same style as the hand-written levels.
+fn map_to_ron(map: &Map) -> String {
+	fn grid<T: ToString>(out: &mut String, name: &str, vals: &[T], width: usize) {
+		out.push_str(&format!("    {}: [\n", name));
+		for row in vals.chunks(width.max(1)) {
+			let row: Vec<String> = row.iter().map(T::to_string).collect();
+			out.push_str(&format!("        {},\n", row.join(",")));
+		}
+		out.push_str("    ],\n");
+	}
+
+	fn list<T: Serialize>(out: &mut String, name: &str, vals: &Option<Vec<T>>) {
+		match vals {
+			Some(vals) => {
+				out.push_str(&format!("    {}: Some([\n", name));
+				for val in vals {
+					out.push_str(&format!("        {},\n", ron::ser::to_string(val).expect("Must ser")));
+				}
+				out.push_str("    ]),\n");
+			},
+			None => {
+				out.push_str(&format!("    {}: None,\n", name));
+			},
+		}
 	}
+
+	let width = map.width as usize;
+	let mut out = String::from("(\n");
+
+	out.push_str(&format!("    width: {},\n", map.width));
+	out.push_str(&format!("    height: {},\n", map.height));
+	grid(&mut out, "tiles", &map.tiles, width);
+	grid(&mut out, "tile_shapes", &map.tile_shapes, width);
+	grid(&mut out, "tile_rots", &map.tile_rots, width);
+	grid(&mut out, "heights", &map.heights, width);
+	out.push_str("    created: false,\n");
+	list(&mut out, "ents", &map.ents);
+	list(&mut out, "walls", &map.walls);
+	list(&mut out, "channels", &map.channels);
+	out.push_str(&format!("    turn_limit: {},\n", ron::ser::to_string(&map.turn_limit).expect("Must ser")));
+	out.push_str(&format!("    ghost_limit: {},\n", ron::ser::to_string(&map.ghost_limit).expect("Must ser")));
+	out.push_str(")\n");
+
+	out
 }
 
 impl Map {
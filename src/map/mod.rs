@@ -1,24 +1,29 @@
+pub mod ascii;
 pub mod materials;
 pub mod meta;
+pub mod save;
+pub mod validation;
+pub mod watch;
 
 use crate::mechanics::audio::SoundClass;
-use crate::mechanics::buttons::{
-	FireSignalOnCollide,
-	OccupySpaceUntilSignal,
-	RegisterSignal,
-};
+use crate::mechanics::buttons::OccupySpaceUntilSignal;
+use crate::mechanics::crates::CrateBody;
+use crate::mechanics::motion::Motion;
+use crate::mechanics::switches::SwitchBody;
 use crate::mechanics::{
-	buttons::SignalCounter,
 	constants::*,
 	ender::Ender,
+	simulation::Simulation,
 	spawner::Spawner,
+	ActiveSimulation,
 	ActiveTurn,
 	Alive,
+	CameraFacer,
+	CollideGridPosition,
 	Direction,
 	DisplayGridPosition,
 	GhostLimit,
 	GridPosition,
-	OccupationMap,
 	Ordinate,
 	TurnLimit,
 };
@@ -30,7 +35,7 @@ use bevy::{
 use enum_primitive::*;
 use lazy_static::lazy_static;
 use materials::AnimatedMaterial;
-use self::meta::Levels;
+use self::meta::{LevelFailure, LevelOverride, Levels};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -155,15 +160,110 @@ lazy_static! {
 	};
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct ActionChannel(pub usize);
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// How many of a channel's sources must be down for it to be met.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Gate {
+	/// Every source.
+	All,
+	/// At least one source.
+	Any,
+	/// No sources at all: doors on this channel close when anything is pressed.
+	Not,
+	/// An odd number of sources.
+	Xor,
+	/// At least this many sources.
+	AtLeast(usize),
+}
+
+impl Gate {
+	pub fn met(self, seen: usize, needed: usize) -> bool {
+		match self {
+			Gate::All => seen >= needed,
+			Gate::Any => seen > 0,
+			Gate::Not => seen == 0,
+			Gate::Xor => seen % 2 == 1,
+			Gate::AtLeast(count) => seen >= count,
+		}
+	}
+}
+
+impl Default for Gate {
+	fn default() -> Self {
+		Gate::All
+	}
+}
+
+/// What a door does when its signal stops while something is standing in it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum DoorClosing {
+	/// Stays open until the way is clear.
+	Wait,
+	/// Shoves whatever's there into a free neighbouring cell, or waits if there isn't one.
+	Push,
+	/// Kills any character there. Crates are too sturdy, so it waits for them.
+	Crush,
+}
+
+impl Default for DoorClosing {
+	fn default() -> Self {
+		DoorClosing::Wait
+	}
+}
+
+/// Kills any character which steps onto it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Hazard {
+	Spikes,
+	Pit,
+	Lava,
+}
+
+impl Hazard {
+	fn colour(self) -> Color {
+		match self {
+			Hazard::Spikes => Color::rgb(0.7, 0.7, 0.75),
+			Hazard::Pit => Color::rgb(0.15, 0.1, 0.1),
+			Hazard::Lava => Color::rgb(1.0, 0.35, 0.05),
+		}
+	}
+}
+
+/// Overrides how a channel is met, and lets other channels act as its sources.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ChannelLogic {
+	pub channel: ActionChannel,
+	#[serde(default)]
+	pub gate: Gate,
+	/// Channels which count as one more source of this one, down whenever they're met.
+	#[serde(default)]
+	pub inputs: Vec<ActionChannel>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum EntData {
 	Start,
 	End,
 	Button(ActionChannel),
 	Door(ActionChannel),
+	/// A door which doesn't just wait for the way to be clear before it closes.
+	HeavyDoor {
+		channel: ActionChannel,
+		closing: DoorClosing,
+	},
+	/// Can be pushed by walking into it, and holds down any button it rests on.
+	Crate,
+	/// Flips on or off each time a character steps onto it.
+	Lever(ActionChannel),
+	/// A button which stays down for `turns` more turns after it's left.
+	TimedPlate {
+		channel: ActionChannel,
+		turns: usize,
+	},
+	/// Ghosts which step onto it are gone until the next loop; the player has to try again.
+	Hazard(Hazard),
 }
 
 impl EntData {
@@ -224,12 +324,53 @@ impl EntData {
 						..Default::default()
 					});
 			},
-			EntData::Button(data) => {
+			EntData::Button(_data) => {
+				let mesh = EntShape::BoostSquare.existing_mesh(&mut meshes);
+
+				comms.spawn((
+						DisplayGridPosition(pos),
+					))
+					.with_bundle(PbrComponents {
+						mesh,
+						material,
+						transform,
+						draw: Draw {
+							is_transparent: true,
+							..Default::default()
+						},
+						..Default::default()
+					});
+			},
+			EntData::Lever(_) | EntData::TimedPlate { .. } => {
+				let mesh = EntShape::BoostSquare.existing_mesh(&mut meshes);
+				let down = match EntAnim::SwitchDown.handles(asset_server, textures, materials) {
+					TexVariety::Unanim(mat) => mat,
+					TexVariety::Anim(mat) => mat.first().unwrap(),
+				};
+
+				comms.spawn((
+						SwitchBody::new(pos, material, down),
+						DisplayGridPosition(pos),
+					))
+					.with_bundle(PbrComponents {
+						mesh,
+						material,
+						transform,
+						draw: Draw {
+							is_transparent: true,
+							..Default::default()
+						},
+						..Default::default()
+					});
+			},
+			EntData::Hazard(hazard) => {
 				let mesh = EntShape::BoostSquare.existing_mesh(&mut meshes);
 
+				if let Some(tinted) = materials.get_mut(&material) {
+					tinted.albedo = hazard.colour();
+				}
+
 				comms.spawn((
-						FireSignalOnCollide::new(data.0, pos),
-						RegisterSignal(data.0),
 						DisplayGridPosition(pos),
 					))
 					.with_bundle(PbrComponents {
@@ -243,11 +384,11 @@ impl EntData {
 						..Default::default()
 					});
 			},
-			EntData::Door(data) => {
+			EntData::Door(_) | EntData::HeavyDoor { .. } => {
 				let mesh = EntShape::BoostSquare.existing_mesh(&mut meshes);
 
 				comms.spawn((
-						OccupySpaceUntilSignal::new(data.0, pos),
+						OccupySpaceUntilSignal::new(pos),
 						DisplayGridPosition(pos),
 					))
 					.with_bundle(PbrComponents {
@@ -261,6 +402,25 @@ impl EntData {
 						..Default::default()
 					});
 			},
+			EntData::Crate => {
+				let mesh = EntShape::Billboard.existing_mesh(&mut meshes);
+
+				comms.spawn((
+						DisplayGridPosition(pos),
+						CollideGridPosition(pos),
+						CameraFacer,
+						Motion::default(),
+					))
+					.with_bundle(PbrComponents {
+						mesh,
+						material,
+						draw: Draw {
+							is_transparent: true,
+							..Default::default()
+						},
+						..Default::default()
+					});
+			},
 		}
 
 		comms.with(Alive::default());
@@ -276,6 +436,11 @@ impl EntData {
 			EntData::End => EntAnim::End,
 			EntData::Button(_) => EntAnim::Button,
 			EntData::Door(_) => EntAnim::Door,
+			EntData::HeavyDoor { .. } => EntAnim::HeavyDoor,
+			EntData::Crate => EntAnim::Crate,
+			EntData::Lever(_) => EntAnim::Lever,
+			EntData::TimedPlate { .. } => EntAnim::TimedPlate,
+			EntData::Hazard(_) => EntAnim::Hazard,
 		}
 	}
 }
@@ -504,8 +669,14 @@ pub enum EntAnim {
 	Button,
 	ButtonGone,
 	Door,
+	HeavyDoor,
 	Char,
 	Ghost,
+	Crate,
+	Lever,
+	TimedPlate,
+	SwitchDown,
+	Hazard,
 }
 }
 
@@ -529,6 +700,9 @@ impl EntAnim {
 			EntAnim::Door => (0.0, &[
 				"assets/tiles/ground_11.png",
 			][..]),
+			EntAnim::HeavyDoor => (0.0, &[
+				"assets/placeholder/door.png",
+			][..]),
 			EntAnim::ButtonGone => (0.0, &[
 				"assets/tiles/ground_14.png",
 			][..]),
@@ -540,20 +714,36 @@ impl EntAnim {
 				"assets/char/ghost1.png",
 				"assets/char/ghost2.png",
 			][..]),
+			EntAnim::Crate => (0.0, &[
+				"assets/tiles/ground_08.png",
+			][..]),
+			EntAnim::Lever => (0.0, &[
+				"assets/placeholder/button.png",
+			][..]),
+			EntAnim::TimedPlate => (0.0, &[
+				"assets/placeholder/bad.png",
+			][..]),
+			EntAnim::SwitchDown => (0.0, &[
+				"assets/placeholder/good.png",
+			][..]),
+			// Tinted to suit the kind of hazard.
+			EntAnim::Hazard => (0.0, &[
+				"assets/placeholder/bad.png",
+			][..]),
 		};
 
 		TexVariety::from_asset_list(fps, res, asset_server, textures, materials)
 	}
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct EntBlueprint {
-	pos: GridPosition,
-	data: EntData,
-	rot: Option<Direction>,
+	pub pos: GridPosition,
+	pub data: EntData,
+	pub rot: Option<Direction>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Wall {
 	pos: GridPosition,
 	h: f32,
@@ -561,7 +751,7 @@ pub struct Wall {
 	rot: Option<Direction>,
 }
 
-#[derive(Clone, Properties, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Properties, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct Map {
 	/// Map width.
 	pub width: Ordinate,
@@ -583,6 +773,8 @@ pub struct Map {
 
 	pub walls: Option<Vec<Wall>>,
 
+	pub channels: Option<Vec<ChannelLogic>>,
+
 	pub turn_limit: TurnLimit,
 
 	pub ghost_limit: Option<GhostLimit>,
@@ -614,11 +806,47 @@ impl Map {
 				},
 			]),
 			walls: None,
+			channels: None,
 			turn_limit: TurnLimit(7),
 			ghost_limit: Some(GhostLimit(1)),
 		}
 	}
 
+	/// Copies this map onto a grid of a new size, dropping anything which no longer fits.
+	///
+	/// Also fills out any short per-tile arrays, so the result is always safe to draw.
+	pub fn resized(&self, width: Ordinate, height: Ordinate) -> Self {
+		let mut out = Self {
+			ents: None,
+			walls: self.walls.clone(),
+			channels: self.channels.clone(),
+			turn_limit: self.turn_limit,
+			ghost_limit: self.ghost_limit,
+			..Self::empty_of_size(width.max(1), height.max(1))
+		};
+
+		for x in 0..out.width.min(self.width) {
+			for y in 0..out.height.min(self.height) {
+				let pos = GridPosition { x, y };
+				let from = pos.unroll(self.width) as usize;
+				let to = pos.unroll(out.width) as usize;
+
+				out.tiles[to] = self.tiles.get(from).copied().unwrap_or_default();
+				out.tile_shapes[to] = self.tile_shapes.get(from).copied().unwrap_or_default();
+				out.tile_rots[to] = self.tile_rots.get(from).copied().unwrap_or_default();
+				out.heights[to] = self.heights.get(from).copied().unwrap_or_default();
+			}
+		}
+
+		out.ents = self.ents.as_ref().map(|ents| ents.iter()
+			.filter(|ent| out.in_bounds(ent.pos))
+			.cloned()
+			.collect()
+		);
+
+		out
+	}
+
 	/// Assumes that positions were chosen by neighbourhood.
 	pub fn move_allowed_by_terrain(&self, former_pos: &GridPosition, next_pos: &GridPosition) -> bool {
 		let dest = next_pos.unroll(self.width) as usize;
@@ -642,6 +870,25 @@ impl Map {
 		}
 	}
 
+	/// Like `move_allowed_by_terrain`, but for something being pushed: it can't
+	/// be shoved up a step any higher than a character could climb, but can drop
+	/// down any height.
+	pub fn push_allowed_by_terrain(&self, former_pos: &GridPosition, next_pos: &GridPosition) -> bool {
+		if !self.in_bounds(*next_pos) || !self.in_bounds(*former_pos) {
+			return false;
+		}
+
+		let height = |pos: &GridPosition| self.heights.get(pos.unroll(self.width) as usize)
+			.map(|v| (*v).into());
+
+		match (height(former_pos), height(next_pos)) {
+			(Some(TileHeight::Passable(s)), Some(TileHeight::Passable(h))) => {
+				h <= s || h - s < HEIGHT_JUMP_LIMIT
+			},
+			_ => false,
+		}
+	}
+
 	fn create_geometry(
 		&self,
 		world: &mut Commands,
@@ -748,9 +995,16 @@ impl Map {
 		textures: &mut ResMut<Assets<Texture>>,
 	) {
 		if let Some(ents) = &self.ents {
+			let mut crates = 0;
 			for blueprint in ents {
 				let rot = blueprint.rot.unwrap_or_default();
-				blueprint.data.create(blueprint.pos, rot, world, meshes, materials, asset_server, textures)
+				blueprint.data.create(blueprint.pos, rot, world, meshes, materials, asset_server, textures);
+
+				// Numbered in the same order as the simulation's.
+				if let EntData::Crate = blueprint.data {
+					world.with(CrateBody(crates));
+					crates += 1;
+				}
 			}
 		}
 	}
@@ -764,6 +1018,10 @@ impl Plugin for MapPlugin {
 	fn build(&self, app: &mut AppBuilder) {
 		app
 			.add_plugin(materials::MaterialPlugin)
+			.add_resource(LevelOverride::default())
+			.add_system(save::save_on_level_exit.system())
+			.add_resource(watch::LevelWatcher::default())
+			.add_system(watch::watch_levels.system())
 			.add_system(map_creator.system());
 	}
 }
@@ -775,9 +1033,10 @@ fn map_creator(
 	mut materials: ResMut<Assets<StandardMaterial>>,
 	asset_server: Res<AssetServer>,
 	mut textures: ResMut<Assets<Texture>>,
-	mut occupation: ResMut<OccupationMap>,
 	mut turn: ResMut<ActiveTurn>,
-	mut signals: ResMut<SignalCounter>,
+	mut sim: ResMut<ActiveSimulation>,
+	mut failure: ResMut<LevelFailure>,
+	level_override: Res<LevelOverride>,
 	mut query: Query<&mut Map>,
 ) {
 	let mut was_empty = true;
@@ -789,19 +1048,32 @@ fn map_creator(
 			map.create_entities(&mut commands, &mut meshes, &mut materials, &asset_server, &mut textures);
 			map.created = true;
 
-			occupation.0 = vec![false; map.len()];
+			sim.0 = Simulation::new(map.clone());
 		}
 		was_empty = false;
 	}
 
 	if was_empty {
 		turn.reinit();
-		signals.reinit();
+		sim.0 = None;
 
-		if level_info.start_at >= level_info.data.len() {
+		if failure.0.is_none() {
+			let edited = level_override.for_level(level_info.start_at)
+				.map(|map| Ok(map.clone()));
 
-		} else {
-			commands.spawn((level_info.load_current(),Alive::default()));
+			let loaded = edited.or_else(|| level_info.data.get(level_info.start_at)
+				.map(|_| level_info.load_current())
+			);
+
+			match loaded {
+				Some(Ok(map)) => {
+					commands.spawn((map, Alive::default()));
+				},
+				Some(Err(e)) => {
+					failure.0 = Some(e);
+				},
+				None => {},
+			}
 		}
 	}
 }
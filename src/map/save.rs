@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use ron::{
+	de::from_reader,
+	ser::{to_string_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	fs::{self, File},
+	io,
+	path::PathBuf,
+};
+
+use crate::mechanics::events::LevelExit;
+
+use super::meta::ManifestPath;
+
+const SAVE_DIR_NAME: &str = "ld47";
+const SAVE_FILE_NAME: &str = "save.ron";
+
+/// The game's own folder in the platform's data directory.
+pub fn data_dir() -> Option<PathBuf> {
+	dirs::data_dir()
+		.map(|dir| dir.join(SAVE_DIR_NAME))
+}
+
+/// Best results for one completed level.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct LevelRecord {
+	/// Fewest turns taken, counting every loop.
+	pub best_turns: usize,
+	/// Fewest loops taken, counting the one which reached the end.
+	pub best_loops: usize,
+}
+
+/// Progress kept between runs of the game.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SaveData {
+	/// Index of the level to start at, as in `Levels::start_at`.
+	pub current: usize,
+	/// Completed levels, keyed by their path.
+	pub completed: BTreeMap<String, LevelRecord>,
+}
+
+impl SaveData {
+	/// Where the save file lives, in the platform's data directory.
+	pub fn location() -> Option<PathBuf> {
+		data_dir()
+			.map(|dir| dir.join(SAVE_FILE_NAME))
+	}
+
+	/// Reads the save file, or starts afresh if there isn't a usable one.
+	pub fn load() -> Self {
+		Self::location()
+			.and_then(|path| File::open(path).ok())
+			.and_then(|f| from_reader(f).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn write(&self) -> io::Result<()> {
+		let path = Self::location()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+
+		fs::write(path, to_string_pretty(self, PrettyConfig::new()).expect("Must ser"))
+	}
+
+	pub fn is_completed(&self, path: &str) -> bool {
+		self.completed.contains_key(path)
+	}
+
+	/// Notes a win, keeping the best of each count.
+	pub fn record(&mut self, path: &str, turns: usize, loops: usize) {
+		let record = self.completed.entry(path.to_string())
+			.or_insert(LevelRecord {
+				best_turns: turns,
+				best_loops: loops,
+			});
+
+		record.best_turns = record.best_turns.min(turns);
+		record.best_loops = record.best_loops.min(loops);
+	}
+}
+
+/// Only progress through the game's own levels is kept.
+pub fn save_on_level_exit(
+	save: Res<SaveData>,
+	manifest_path: Res<ManifestPath>,
+	evts: Res<Events<LevelExit>>,
+) {
+	if !manifest_path.is_default() {
+		return;
+	}
+
+	if evts.get_reader().iter(&evts).next().is_some() {
+		if let Err(e) = save.write() {
+			eprintln!("Could not save progress: {}", e);
+		}
+	}
+}
@@ -0,0 +1,373 @@
+use crate::mechanics::{
+	Direction,
+	GridPosition,
+	Ordinate,
+};
+
+use super::{
+	EntData,
+	Gate,
+	Map,
+	TileHeight,
+	TileShape,
+	TileTexture,
+};
+
+use enum_primitive::*;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MapError {
+	/// Width or height is zero or negative.
+	BadDimensions {
+		width: Ordinate,
+		height: Ordinate,
+	},
+	/// A per-tile layer doesn't have exactly `width * height` entries.
+	LayerLength {
+		layer: &'static str,
+		expected: usize,
+		found: usize,
+	},
+	UnknownTexture {
+		pos: GridPosition,
+		value: u8,
+	},
+	UnknownShape {
+		pos: GridPosition,
+		value: u8,
+	},
+	UnknownRotation {
+		pos: GridPosition,
+		value: u8,
+	},
+	UnknownWallTexture {
+		pos: GridPosition,
+		value: u8,
+	},
+	EntOutOfBounds {
+		pos: GridPosition,
+	},
+	/// Nothing feeds this door's channel, so it can never open.
+	DoorWithoutButton {
+		pos: GridPosition,
+		channel: usize,
+	},
+	/// A crate sits on impassable terrain, or on the Start.
+	CrateCannotRest {
+		pos: GridPosition,
+	},
+	/// Every loop would begin with a death.
+	HazardOnStart {
+		pos: GridPosition,
+	},
+	/// More than one entry in `channels` describes this channel.
+	DuplicateChannelLogic {
+		channel: usize,
+	},
+	/// This channel is (perhaps indirectly) one of its own inputs.
+	ChannelCycle {
+		channel: usize,
+	},
+	/// An `AtLeast` gate which needs more sources than feed its channel, so it's never met.
+	UnreachableGate {
+		channel: usize,
+		needed: usize,
+		sources: usize,
+	},
+	NoStart,
+	MultipleStarts(Vec<GridPosition>),
+	NoEnd,
+}
+
+impl fmt::Display for MapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		use MapError::*;
+		match self {
+			BadDimensions { width, height } =>
+				write!(f, "map size {}x{} is not valid", width, height),
+			LayerLength { layer, expected, found } =>
+				write!(f, "`{}` has {} entries, expected {}", layer, found, expected),
+			UnknownTexture { pos, value } =>
+				write!(f, "unknown tile texture {} at {}", value, pos),
+			UnknownShape { pos, value } =>
+				write!(f, "unknown tile shape {} at {}", value, pos),
+			UnknownRotation { pos, value } =>
+				write!(f, "unknown tile rotation {} at {}", value, pos),
+			UnknownWallTexture { pos, value } =>
+				write!(f, "unknown wall texture {} at {}", value, pos),
+			EntOutOfBounds { pos } =>
+				write!(f, "entity at {} is outside the map", pos),
+			DoorWithoutButton { pos, channel } =>
+				write!(f, "door at {} is on channel {}, which has no button, lever, plate or inputs", pos, channel),
+			CrateCannotRest { pos } =>
+				write!(f, "crate at {} must sit on passable ground, away from the Start", pos),
+			HazardOnStart { pos } =>
+				write!(f, "hazard at {} is on the Start", pos),
+			DuplicateChannelLogic { channel } =>
+				write!(f, "channel {} has more than one entry in `channels`", channel),
+			ChannelCycle { channel } =>
+				write!(f, "channel {} depends on itself through its inputs", channel),
+			UnreachableGate { channel, needed, sources } =>
+				write!(f, "channel {} needs {} of its sources down, but only has {}", channel, needed, sources),
+			NoStart =>
+				write!(f, "map has no Start"),
+			MultipleStarts(positions) => {
+				write!(f, "map has {} Starts, at", positions.len())?;
+				for pos in positions {
+					write!(f, " {}", pos)?;
+				}
+				Ok(())
+			},
+			NoEnd =>
+				write!(f, "map has no End"),
+		}
+	}
+}
+
+impl Map {
+	/// Checks that this map can be loaded and played, listing every problem found.
+	pub fn validate(&self) -> Vec<MapError> {
+		let mut out = vec![];
+
+		if self.width <= 0 || self.height <= 0 {
+			out.push(MapError::BadDimensions {
+				width: self.width,
+				height: self.height,
+			});
+
+			return out;
+		}
+
+		let expected = self.len();
+		let layers = [
+			("tiles", self.tiles.len()),
+			("tile_shapes", self.tile_shapes.len()),
+			("tile_rots", self.tile_rots.len()),
+			("heights", self.heights.len()),
+		];
+
+		for &(layer, found) in layers.iter() {
+			if found != expected {
+				out.push(MapError::LayerLength {
+					layer,
+					expected,
+					found,
+				});
+			}
+		}
+
+		for (i, &value) in self.tiles.iter().enumerate().take(expected) {
+			if TileTexture::from_u8(value).is_none() {
+				out.push(MapError::UnknownTexture { pos: self.cell_pos(i), value });
+			}
+		}
+
+		for (i, &value) in self.tile_shapes.iter().enumerate().take(expected) {
+			if TileShape::from_u8(value).is_none() {
+				out.push(MapError::UnknownShape { pos: self.cell_pos(i), value });
+			}
+		}
+
+		for (i, &value) in self.tile_rots.iter().enumerate().take(expected) {
+			if Direction::from_u8(value).is_none() {
+				out.push(MapError::UnknownRotation { pos: self.cell_pos(i), value });
+			}
+		}
+
+		for wall in self.walls.iter().flatten() {
+			if TileTexture::from_u8(wall.texture).is_none() {
+				out.push(MapError::UnknownWallTexture { pos: wall.pos, value: wall.texture });
+			}
+		}
+
+		let mut starts = vec![];
+		let mut has_end = false;
+		// How many buttons, levers, plates and inputs feed each channel.
+		let mut sources: HashMap<usize, usize> = HashMap::new();
+
+		for blueprint in self.ents.iter().flatten() {
+			let pos = blueprint.pos;
+			if !self.in_bounds(pos) {
+				out.push(MapError::EntOutOfBounds { pos });
+			}
+
+			match &blueprint.data {
+				EntData::Start => starts.push(pos),
+				EntData::End => has_end = true,
+				EntData::Button(channel)
+				| EntData::Lever(channel)
+				| EntData::TimedPlate { channel, .. } => {
+					*sources.entry(channel.0).or_default() += 1;
+				},
+				EntData::Door(_) | EntData::HeavyDoor { .. } | EntData::Crate | EntData::Hazard(_) => {},
+			}
+		}
+
+		for blueprint in self.ents.iter().flatten() {
+			if let EntData::Hazard(_) = blueprint.data {
+				if starts.contains(&blueprint.pos) {
+					out.push(MapError::HazardOnStart { pos: blueprint.pos });
+				}
+			}
+
+			if let EntData::Crate = blueprint.data {
+				let pos = blueprint.pos;
+				let passable = self.in_bounds(pos) && self.heights.get(pos.unroll(self.width) as usize)
+					.map(|h| matches!(TileHeight::from(*h), TileHeight::Passable(_)))
+					.unwrap_or(false);
+
+				if !passable || starts.contains(&pos) {
+					out.push(MapError::CrateCannotRest { pos });
+				}
+			}
+		}
+
+		let mut described = HashSet::new();
+		for rule in self.channels.iter().flatten() {
+			if !described.insert(rule.channel.0) {
+				out.push(MapError::DuplicateChannelLogic { channel: rule.channel.0 });
+			}
+
+			if !rule.inputs.is_empty() {
+				*sources.entry(rule.channel.0).or_default() += rule.inputs.len();
+			}
+
+			if self.channel_feeds(rule.channel.0, rule.channel.0, &mut HashSet::new()) {
+				out.push(MapError::ChannelCycle { channel: rule.channel.0 });
+			}
+		}
+
+		for rule in self.channels.iter().flatten() {
+			if let Gate::AtLeast(needed) = rule.gate {
+				let count = sources.get(&rule.channel.0).copied().unwrap_or_default();
+				if needed > count {
+					out.push(MapError::UnreachableGate {
+						channel: rule.channel.0,
+						needed,
+						sources: count,
+					});
+				}
+			}
+		}
+
+		// A gate which is met with nothing down (e.g., `Not`) holds its doors open without any sources.
+		let open_without_sources: HashSet<usize> = self.channels.iter().flatten()
+			.filter(|rule| rule.gate.met(0, 0))
+			.map(|rule| rule.channel.0)
+			.collect();
+
+		for blueprint in self.ents.iter().flatten() {
+			if let EntData::Door(channel) | EntData::HeavyDoor { channel, .. } = &blueprint.data {
+				if !sources.contains_key(&channel.0) && !open_without_sources.contains(&channel.0) {
+					out.push(MapError::DoorWithoutButton {
+						pos: blueprint.pos,
+						channel: channel.0,
+					});
+				}
+			}
+		}
+
+		match starts.len() {
+			0 => out.push(MapError::NoStart),
+			1 => {},
+			_ => out.push(MapError::MultipleStarts(starts)),
+		}
+
+		if !has_end {
+			out.push(MapError::NoEnd);
+		}
+
+		out
+	}
+
+	/// Whether `from` is an input of `to`, directly or through other channels.
+	fn channel_feeds(&self, from: usize, to: usize, visited: &mut HashSet<usize>) -> bool {
+		if !visited.insert(to) {
+			return false;
+		}
+
+		self.channels.iter().flatten()
+			.filter(|rule| rule.channel.0 == to)
+			.flat_map(|rule| rule.inputs.iter())
+			.any(|input| input.0 == from || self.channel_feeds(from, input.0, visited))
+	}
+
+	pub fn in_bounds(&self, pos: GridPosition) -> bool {
+		pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height
+	}
+
+	/// Position of the `i`th entry in each per-tile layer.
+	fn cell_pos(&self, i: usize) -> GridPosition {
+		let i = i as Ordinate;
+		GridPosition {
+			x: i % self.width,
+			y: i / self.width,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::{ActionChannel, ChannelLogic};
+	use crate::map::ascii::{AsciiMap, TileKind};
+	use crate::mechanics::TurnLimit;
+
+	/// A corridor from the Start to the End, with a door and a lever beside it.
+	fn map(channels: Vec<ChannelLogic>) -> Map {
+		AsciiMap {
+			legend: vec![
+				('a', TileKind { height: 0, ..Default::default() }),
+				('x', TileKind { height: -1, ..Default::default() }),
+			].into_iter().collect(),
+			grid: vec!["aS aL0 a aE".into(), "x aD1 aD2 x".into()],
+			walls: None,
+			channels: Some(channels),
+			turn_limit: TurnLimit(8),
+			ghost_limit: None,
+		}.into_map().unwrap()
+	}
+
+	fn rule(channel: usize, gate: Gate, inputs: &[usize]) -> ChannelLogic {
+		ChannelLogic {
+			channel: ActionChannel(channel),
+			gate,
+			inputs: inputs.iter().copied().map(ActionChannel).collect(),
+		}
+	}
+
+	#[test]
+	fn chained_channels_are_valid() {
+		let map = map(vec![rule(1, Gate::Any, &[0]), rule(2, Gate::All, &[0, 1])]);
+		assert_eq!(map.validate(), vec![]);
+	}
+
+	#[test]
+	fn channel_cycles_are_rejected() {
+		let map = map(vec![rule(1, Gate::Any, &[0, 2]), rule(2, Gate::Any, &[1])]);
+		assert_eq!(map.validate(), vec![
+			MapError::ChannelCycle { channel: 1 },
+			MapError::ChannelCycle { channel: 2 },
+		]);
+	}
+
+	#[test]
+	fn at_least_gates_need_enough_sources() {
+		let map = map(vec![rule(1, Gate::AtLeast(2), &[0]), rule(2, Gate::AtLeast(1), &[0])]);
+		assert_eq!(map.validate(), vec![
+			MapError::UnreachableGate { channel: 1, needed: 2, sources: 1 },
+		]);
+	}
+
+	#[test]
+	fn doors_need_a_source_unless_their_gate_is_met_without_one() {
+		let map = map(vec![rule(1, Gate::Any, &[]), rule(2, Gate::Not, &[])]);
+		assert_eq!(map.validate(), vec![
+			MapError::DoorWithoutButton { pos: GridPosition { x: 1, y: 1 }, channel: 1 },
+		]);
+	}
+}
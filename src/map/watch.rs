@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use std::{fs, time::SystemTime};
+
+use crate::mechanics::Alive;
+use crate::mechanics::ender::trigger_restart;
+
+use super::meta::{LevelFailure, LevelInfo, LevelOverride, Levels, ManifestPath};
+
+const WATCH_INTERVAL_SECS: f32 = 0.5;
+
+/// Last-seen modification times of the manifest and the level in play.
+pub struct LevelWatcher {
+	timer: Timer,
+	primed: bool,
+	manifest: Option<SystemTime>,
+	level: Option<(String, Option<SystemTime>)>,
+}
+
+impl Default for LevelWatcher {
+	fn default() -> Self {
+		Self {
+			timer: Timer::from_seconds(WATCH_INTERVAL_SECS, true),
+			primed: false,
+			manifest: None,
+			level: None,
+		}
+	}
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+	fs::metadata(path)
+		.and_then(|meta| meta.modified())
+		.ok()
+}
+
+/// Reloads the manifest and respawns the current level when either file changes on disk.
+pub fn watch_levels(
+	time: Res<Time>,
+	manifest_path: Res<ManifestPath>,
+	mut watcher: ResMut<LevelWatcher>,
+	mut levels: ResMut<Levels>,
+	mut level_override: ResMut<LevelOverride>,
+	mut failure: ResMut<LevelFailure>,
+	mut ents_query: Query<&mut Alive>,
+) {
+	watcher.timer.tick(time.delta_seconds);
+	if !watcher.timer.just_finished {
+		return;
+	}
+
+	let manifest = modified(&manifest_path.0);
+	let manifest_changed = watcher.primed && manifest != watcher.manifest;
+	watcher.manifest = manifest;
+
+	let mut manifest_failure = None;
+	if manifest_changed {
+		match Levels::from_path(&manifest_path.0) {
+			Ok(mut fresh) => {
+				fresh.start_at = levels.start_at.min(fresh.data.len());
+				*levels = fresh;
+			},
+			Err(e) => {
+				manifest_failure = Some(e);
+			},
+		}
+	}
+
+	// A level from outside the manifest is only known by its override.
+	let outside = level_override.path_for(levels.start_at)
+		.map(String::from);
+
+	let level = outside.clone().or_else(|| levels.data.get(levels.start_at)
+		.map(|info| info.path.clone()));
+	let stamp = level.as_ref().and_then(|path| modified(path));
+
+	// Moving on to another level isn't a change to reload for.
+	let level_changed = watcher.primed && match &watcher.level {
+		Some((path, old)) => Some(path) == level.as_ref() && *old != stamp,
+		None => false,
+	};
+
+	watcher.level = level.map(|path| (path, stamp));
+	watcher.primed = true;
+
+	// What's on disk now replaces any edited copy of the level.
+	let mut level_failure = None;
+	match outside.filter(|_| level_changed) {
+		Some(path) => {
+			match (LevelInfo { name: String::new(), path: path.clone() }).get_map() {
+				Ok(map) => {
+					*level_override = LevelOverride::outside(levels.start_at, map, &path);
+				},
+				Err(e) => {
+					level_failure = Some(e);
+				},
+			}
+		},
+		None if level_changed && level_override.for_level(levels.start_at).is_some() => {
+			*level_override = LevelOverride::default();
+		},
+		None => {},
+	}
+
+	if manifest_changed || level_changed {
+		failure.0 = manifest_failure.or(level_failure);
+		trigger_restart(&mut ents_query);
+	}
+}
@@ -1,32 +1,30 @@
 use bevy::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::map::Map;
+use crate::map::{ChannelLogic, Gate};
 
+use super::ActiveSimulation;
 use super::CollideGridPosition;
 use super::DisplayGridPosition;
 use super::GridPosition;
-use super::OccupationMap;
 use super::audio::SoundClass;
 
 pub struct ButtonPlugin;
 
 impl Plugin for ButtonPlugin {
 	fn build(&self, app: &mut AppBuilder) {
-		app.add_resource(SignalCounter::default())
-			.add_system(fire_signal_on_collide.system())
-			.add_system(block_until_signal.system())
-			.add_system(register_signal_sources.system());
+		app.add_system(block_until_signal.system());
 	}
 }
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default)]
 pub struct SignalCount {
 	seen: usize,
 	needed: usize,
+	gate: Gate,
 }
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default)]
 pub struct SignalCounter(pub HashMap<usize, SignalCount>);
 
 impl SignalCounter {
@@ -47,15 +45,9 @@ impl SignalCounter {
 		// println!("incrementing signal {} -> {} seen", signal, count.seen);
 	}
 
-	pub fn decrement_signal(&mut self, signal: usize) {
-		let mut count = self.ensure_exists(signal);
-		count.seen -= 1;
-		// println!("decrementing signal {} -> {} seen", signal, count.seen);
-	}
-
 	pub fn signal_met(&self, signal: usize) -> bool {
 		let out = self.0.get(&signal)
-			.map(|counter| counter.seen >= counter.needed)
+			.map(|counter| counter.gate.met(counter.seen, counter.needed))
 			.unwrap_or_default();
 
 		// println!("query signal {} -> {}", signal, out);
@@ -66,81 +58,90 @@ impl SignalCounter {
 	pub fn reinit(&mut self) {
 		self.0 = Default::default();
 	}
-}
 
-#[derive(Debug, Default)]
-pub struct RegisterSignal(pub usize);
+	/// Applies each channel's gate, and feeds channels into the channels which take them as inputs.
+	///
+	/// Call once every button, lever and plate has been counted.
+	pub fn apply_logic(&mut self, logic: &[ChannelLogic]) {
+		for rule in logic.iter() {
+			self.ensure_exists(rule.channel.0).gate = rule.gate;
+		}
 
-#[derive(Debug, Default)]
-pub struct FireSignalOnCollide {
-	signal: usize,
-	pos: GridPosition,
-	last_collide: bool,
-}
+		let mut resolved = HashSet::new();
+		for rule in logic.iter() {
+			self.resolve(rule.channel.0, logic, &mut resolved);
+		}
+	}
 
-impl FireSignalOnCollide {
-	pub fn new(signal: usize, pos: GridPosition) -> Self {
-		Self {
-			signal,
-			pos,
-			..Default::default()
+	fn resolve(&mut self, signal: usize, logic: &[ChannelLogic], resolved: &mut HashSet<usize>) {
+		// Also stops cycles (which validation rejects) from recursing forever.
+		if !resolved.insert(signal) {
+			return;
+		}
+
+		let inputs = logic.iter()
+			.filter(|rule| rule.channel.0 == signal)
+			.flat_map(|rule| rule.inputs.iter());
+
+		for input in inputs {
+			self.resolve(input.0, logic, resolved);
+
+			let met = self.signal_met(input.0);
+			self.register_signal_source(signal);
+			if met {
+				self.increment_signal(signal);
+			}
 		}
 	}
 }
 
 #[derive(Debug, Default)]
 pub struct OccupySpaceUntilSignal{
-	signal: usize,
 	pos: GridPosition,
 	last_collide: bool,
+	last_held: bool,
 }
 
 impl OccupySpaceUntilSignal {
-	pub fn new(signal: usize, pos: GridPosition) -> Self {
+	pub fn new(pos: GridPosition) -> Self {
 		Self {
-			signal,
 			pos,
 			last_collide: true,
-		}
-	}
-}
-
-fn fire_signal_on_collide(
-	mut signals: ResMut<SignalCounter>,
-	collisions: Res<OccupationMap>,
-	mut maps: Query<&Map>,
-	mut query: Query<&mut FireSignalOnCollide>,
-) {
-	for map in &mut maps.iter() {
-		for mut signaller in &mut query.iter() {
-			let pos = signaller.pos.unroll(map.width) as usize;
-			let occupied = collisions.0[pos];
-
-			if signaller.last_collide != occupied {
-				if occupied {
-					signals.increment_signal(signaller.signal);
-				} else {
-					signals.decrement_signal(signaller.signal);
-				}
-				signaller.last_collide = occupied;
-			}
+			last_held: false,
 		}
 	}
 }
 
 fn block_until_signal(
 	mut commands: Commands,
-	signals: ResMut<SignalCounter>,
-	mut collisions: ResMut<OccupationMap>,
+	sim: Res<ActiveSimulation>,
 	mut evts: ResMut<Events<SoundClass>>,
-	mut maps: Query<&Map>,
 	mut query: Query<(Entity, &mut OccupySpaceUntilSignal, &mut Transform)>,
 ) {
-	for map in &mut maps.iter() {
+	if let Some(sim) = &sim.0 {
 		for (ent, mut collide_data, mut tx) in &mut query.iter() {
-			let pos = collide_data.pos.unroll(map.width) as usize;
-			let cond_met = signals.signal_met(collide_data.signal);
-			if cond_met != collide_data.last_collide {
+			// A held door is still open, but is drawn in place to show it's trying to close.
+			let held = sim.door_held(collide_data.pos);
+			let cond_met = sim.door_open(collide_data.pos) && !held;
+			let was_held = collide_data.last_held;
+
+			if held != was_held {
+				if held {
+					// It may have opened and been held within the same turn.
+					if !collide_data.last_collide {
+						commands.remove_one::<CollideGridPosition>(ent);
+					}
+					commands.insert_one(ent, DisplayGridPosition(collide_data.pos));
+				} else if cond_met {
+					commands.remove_one::<DisplayGridPosition>(ent);
+					tx.set_translation(Vec3::new(-22.0, -22.0, -22.0));
+				} else {
+					commands.insert_one(ent, CollideGridPosition(collide_data.pos));
+				}
+
+				collide_data.last_held = held;
+				collide_data.last_collide = cond_met;
+			} else if !held && cond_met != collide_data.last_collide {
 				if cond_met {
 					commands.remove_one::<CollideGridPosition>(ent);
 					commands.remove_one::<DisplayGridPosition>(ent);
@@ -154,19 +155,6 @@ fn block_until_signal(
 
 				collide_data.last_collide = cond_met;
 			}
-
-			collisions.0[pos] = cond_met;
 		}
 	}
 }
-
-fn register_signal_sources(
-	mut commands: Commands,
-	mut signals: ResMut<SignalCounter>,
-	mut query: Query<(Entity, &RegisterSignal)>,
-) {
-	for (ent, signal) in &mut query.iter() {
-		signals.register_signal_source(signal.0);
-		commands.remove_one::<RegisterSignal>(ent);
-	}
-}
@@ -1,7 +1,8 @@
+use super::controls::Action;
 use super::events::DoLevelGen;
 use super::{
 	character::ActiveCharacter,
-	CameraFaced, DisplayGridPosition, GridPosition
+	CameraFaced, ControlsLocked, DisplayGridPosition, GridPosition
 };
 use crate::map::Map;
 use crate::map::WORLD_HEIGHT_SCALE;
@@ -197,20 +198,20 @@ fn tick_camera_mode(
 fn slide_camera_to_dest(
 	dest: Res<CameraDest>,
 	mode: Res<CameraMode>,
-	input: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
 	mut maps: Query<&Map>,
 	mut cameras: Query<(&CameraFaced, &mut Transform)>,
 ) {
 	let mut offset = Vec3::new(-2.0, 2.0, -2.0);
 
 	if mode.allow_pan() {
-		if input.pressed(KeyCode::Z) {
+		if actions.pressed(Action::CameraLeft) {
 			offset = Mat3::from_rotation_y(-std::f32::consts::FRAC_PI_6).mul_vec3(offset);
-		} else if input.pressed(KeyCode::C) {
+		} else if actions.pressed(Action::CameraRight) {
 			offset = Mat3::from_rotation_y(std::f32::consts::FRAC_PI_6).mul_vec3(offset);
 		}
 
-		if input.pressed(KeyCode::X) {
+		if actions.pressed(Action::CameraUp) {
 			offset[1] += 3.0;
 		}
 	}
@@ -220,7 +221,9 @@ fn slide_camera_to_dest(
 	if let Some(dest) = dest.0 {
 		for map in &mut maps.iter() {
 			for (_tag, mut tx) in &mut cameras.iter() {
-				let z_target = map.heights[dest.unroll(map.width) as usize];
+				let z_target = map.heights.get(dest.unroll(map.width) as usize)
+					.copied()
+					.unwrap_or_default();
 				let target = Vec3::new(-dest.y as f32, (z_target as f32) * WORLD_HEIGHT_SCALE, dest.x as f32);
 
 				let start = tx.value();
@@ -244,9 +247,14 @@ fn slide_camera_to_dest(
 }
 
 fn active_char_is_camera_dest(
+	locked: Res<ControlsLocked>,
 	mut dest: ResMut<CameraDest>,
 	mut chars: Query<(&ActiveCharacter, &DisplayGridPosition)>,
 ) {
+	if locked.0 {
+		return;
+	}
+
 	for (_tag, pos) in &mut chars.iter() {
 		dest.0 = Some(pos.0);
 	}
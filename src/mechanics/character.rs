@@ -3,21 +3,28 @@ use super::Alive;
 use super::GhostLimit;
 use super::audio::SoundClass;
 use super::audio::StepEvent;
+use super::camera::CameraMode;
+use super::controls::{Action, InputMap};
+use super::motion::{BumpEvent, Motion};
+use super::playback::TurnPlayback;
+use super::simulation::{DoorEvent, MoveOutcome, SimStatus, TurnReport};
 use super::{
+	ActiveSimulation,
 	ActiveTurn,
 	CameraFacer,
 	CollideGridPosition,
+	ControlsLocked,
 	Direction,
 	DisplayGridPosition,
 	GridPosition,
-	OccupationMap,
 	Ordinate,
-	TurnLimit,
 };
 use crate::map::materials::AnimatedMaterial;
-use crate::map::{EntAnim, EntShape, Map, TexVariety};
+use crate::map::{EntAnim, EntShape, TexVariety};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum CharacterCommand {
 	Move(Direction),
 	Wait,
@@ -30,6 +37,8 @@ pub struct Character {
 	pub command_list: Vec<CharacterCommand>,
 	pub cmd_list_pos: usize,
 	pub my_turn: usize,
+	/// Out of play until the next loop.
+	pub dead: bool,
 }
 
 impl Character {
@@ -40,6 +49,7 @@ impl Character {
 			command_list: vec![],
 			cmd_list_pos: 0,
 			my_turn: 0,
+			dead: false,
 		}
 	}
 
@@ -47,36 +57,10 @@ impl Character {
 		Self::new(GridPosition{ x, y })
 	}
 
-	pub fn do_action(&mut self, action: CharacterCommand, map: &Map, colliders: &mut OccupationMap) -> Option<GridPosition> {
-		let supposed_dest = self.current.destination(action);
-
-		// println!("{:?} -> {:?}", action, supposed_dest);
-		let modif = supposed_dest.clamp(map.width, map.height);
-		let normalised = modif.unroll(map.width) as usize;
-
-		if map.move_allowed_by_terrain(&self.current, &supposed_dest) && !colliders.0[normalised] {
-			let form_norm = self.current.clamp(map.width, map.height).unroll(map.width) as usize;
-			colliders.move_collider(form_norm, normalised);
-			self.current = supposed_dest;
-			// println!("moved to {:?}", supposed_dest);
-
-			Some(modif)
-		} else {
-			// println!("move blocked");
-			None
-		}
-	}
-
-	pub fn do_queued_action(&mut self, map: &Map, colliders: &mut OccupationMap) {
-		// println!("Queue!");
-		let action = self.command_list[self.cmd_list_pos];
-		self.do_action(action, map, colliders);
-		self.cmd_list_pos += 1;
-	}
-
 	pub fn reset(&mut self) {
 		self.current = self.start;
 		self.cmd_list_pos = 0;
+		self.dead = false;
 	}
 
 	pub fn new_me(&self) -> Self {
@@ -113,6 +97,7 @@ impl Character {
 				DisplayGridPosition(pos),
 				CollideGridPosition(pos),
 				CameraFacer,
+				Motion::default(),
 			))
 			.with_bundle(PbrComponents {
 				mesh,
@@ -134,8 +119,9 @@ pub struct CharacterPlugin;
 
 impl Plugin for CharacterPlugin {
 	fn build(&self, app: &mut AppBuilder) {
-		app.add_system(char_control.system())
-			.add_system(char_act.system())
+		app.add_resource(InputQueue::default())
+			.add_system(char_control.system())
+			.add_system(char_sync.system())
 			.add_system(char_display.system())
 			.add_system(char_reset.system())
 			.add_system(char_set_collision.system());
@@ -146,84 +132,230 @@ pub struct ActiveCharacter;
 
 pub struct InactiveCharacter;
 
+/// Moves the player has asked for but the active character hasn't taken yet, oldest first.
+#[derive(Debug, Default)]
+pub struct InputQueue {
+	pending: VecDeque<CharacterCommand>,
+	/// The pending moves are a clicked path, so give up rather than walk into anyone.
+	cautious: bool,
+}
+
+impl InputQueue {
+	/// Keeps the earliest presses when full: they're the ones the player is waiting on.
+	pub fn push(&mut self, command: CharacterCommand, max_depth: usize) {
+		if self.cautious {
+			self.clear();
+		}
+
+		if self.pending.len() < max_depth.max(1) {
+			self.pending.push_back(command);
+		}
+	}
+
+	/// Replaces anything pending with a whole route.
+	pub fn set_path(&mut self, path: &[Direction]) {
+		self.pending = path.iter()
+			.map(|dir| CharacterCommand::Move(*dir))
+			.collect();
+		self.cautious = true;
+	}
+
+	pub fn clear(&mut self) {
+		self.pending.clear();
+		self.cautious = false;
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+}
+
+fn command_for(action: Action) -> Option<CharacterCommand> {
+	use CharacterCommand::*;
+	match action {
+		Action::MoveNorth => Some(Move(Direction::North)),
+		Action::MoveEast => Some(Move(Direction::East)),
+		Action::MoveSouth => Some(Move(Direction::South)),
+		Action::MoveWest => Some(Move(Direction::West)),
+		Action::Wait => Some(Wait),
+		_ => None,
+	}
+}
+
 fn char_control(
-	limit: Res<TurnLimit>,
-	mut occupation: ResMut<OccupationMap>,
-	mut turn: ResMut<ActiveTurn>,
-	key_input: Res<Input<KeyCode>>,
+	mut sim: ResMut<ActiveSimulation>,
+	locked: Res<ControlsLocked>,
+	mode: Res<CameraMode>,
+	map: Res<InputMap>,
+	actions: Res<Input<Action>>,
+	mut queue: ResMut<InputQueue>,
+	mut playback: ResMut<TurnPlayback>,
 	mut evts: ResMut<Events<StepEvent>>,
 	mut sound_evts: ResMut<Events<SoundClass>>,
-	mut map_query: Query<&Map>,
-	mut query: Query<(&mut Character, &ActiveCharacter)>,
+	mut bump_evts: ResMut<Events<BumpEvent>>,
 ) {
-	for map in &mut map_query.iter() {
-		for (mut character, _active) in &mut query.iter() {
-			let mut chosen_dir = None;
-
-			for key in key_input.get_just_pressed() {
-				use CharacterCommand::*;
-				match key {
-					KeyCode::Up => {
-						chosen_dir = Some(Move(Direction::North));
-					},
-					KeyCode::Right => {
-						chosen_dir = Some(Move(Direction::East));
-					},
-					KeyCode::Left => {
-						chosen_dir = Some(Move(Direction::West));
-					},
-					KeyCode::Down => {
-						chosen_dir = Some(Move(Direction::South));
-					},
-					KeyCode::Space => {
-						chosen_dir = Some(Wait);
-					},
-					_ => {},
-				}
-			}
+	if locked.0 {
+		queue.clear();
+		return;
+	}
 
-			if let Some(action) = chosen_dir {
-				if turn.allow_turn(limit.0, character.my_turn) {
-					// ALWAYS push action regardless of whether or not it is doable.
-					character.command_list.push(action);
+	let sim = match &mut sim.0 {
+		Some(sim) => sim,
+		None => {
+			queue.clear();
+			playback.clear();
+			return;
+		},
+	};
+
+	if actions.just_pressed(Action::Undo) {
+		queue.clear();
+		playback.clear();
+		if !sim.undo() {
+			sound_evts.send(SoundClass::Blocked);
+		}
+		return;
+	}
+
+	if actions.just_pressed(Action::Rewind) {
+		queue.clear();
+		playback.clear();
+		if !sim.rewind_loop() {
+			sound_evts.send(SoundClass::Blocked);
+		}
+		return;
+	}
 
-					if let Some(pos) = character.do_action(action, map, &mut occupation) {
-						evts.send(StepEvent(pos));
-					} else {
-						sound_evts.send(SoundClass::Blocked);
-					}
+	// Any move after dying is a quick retry of the loop.
+	if sim.status() == SimStatus::Died {
+		queue.clear();
+		if actions.get_just_pressed().any(|action| command_for(*action).is_some()) {
+			playback.clear();
+			sim.rewind_loop();
+		}
+		return;
+	}
+
+	for command in actions.get_just_pressed().filter_map(|action| command_for(*action)) {
+		queue.push(command, map.queue_depth);
+	}
 
-					turn.march_turn();
-				}
+	if !sim.is_running() {
+		queue.clear();
+		return;
+	}
+
+	// One move per frame, none while the camera is flying between levels,
+	// and none until every ghost has been seen taking its last one.
+	if mode.allow_pan() && playback.is_idle() {
+		if let Some(command) = queue.pending.pop_front() {
+			// Someone has stepped into the route since it was planned.
+			let dest = sim.active().current.destination(command);
+			let occupied = sim.occupation().0
+				.get(dest.unroll(sim.map().width) as usize)
+				.copied()
+				.unwrap_or(false);
+
+			if queue.cautious && occupied {
+				queue.clear();
+				sound_evts.send(SoundClass::Blocked);
+				return;
+			}
+
+			let report = sim.step(command);
+			announce_turn(&report, &mut playback, map.ghost_delay, &mut evts, &mut sound_evts, &mut bump_evts);
+
+			// A route means nothing to the next loop's character.
+			if report.looped && queue.cautious {
+				queue.clear();
 			}
-		}	
+		}
 	}
 }
 
-fn char_act(
-	limit: Res<TurnLimit>,
-	mut turn: ResMut<ActiveTurn>,
-	mut occupation: ResMut<OccupationMap>,
-	mut map_query: Query<&Map>,
-	mut query: Query<(&mut Character, &InactiveCharacter)>,
+/// Plays the sound of the active character's part of a turn (and of any doors closing on
+/// someone, or anyone dying), and bumps anyone who walked into something.
+///
+/// The ghosts' part is handed to `playback` to be shown one at a time, `delay` seconds apart.
+pub fn announce_turn(
+	report: &TurnReport,
+	playback: &mut TurnPlayback,
+	delay: f32,
+	evts: &mut Events<StepEvent>,
+	sound_evts: &mut Events<SoundClass>,
+	bump_evts: &mut Events<BumpEvent>,
 ) {
-	for map in &mut map_query.iter() {
-		for (mut character, _inactive) in &mut query.iter() {
-			if turn.allow_turn(limit.0, character.my_turn) {
-				// ALWAYS push action regardless of whether or not it is doable.
-				character.do_queued_action(map, &mut occupation);
+	// The active character always acts first.
+	if let Some(subturn) = report.subturns.first() {
+		match subturn.outcome {
+			MoveOutcome::Moved(pos) => {
+				evts.send(StepEvent(pos));
+			},
+			MoveOutcome::Blocked | MoveOutcome::Waited => {
+				sound_evts.send(SoundClass::Blocked);
+			},
+		}
+	}
+
+	for event in report.doors.iter() {
+		match event {
+			DoorEvent::Held(_) => {
+				sound_evts.send(SoundClass::Blocked);
+			},
+			DoorEvent::Pushed { .. } => {
+				sound_evts.send(SoundClass::Stone);
+			},
+			DoorEvent::Crushed { .. } => {
+				sound_evts.send(SoundClass::Stone);
+			},
+		}
+	}
+
+	for _ in report.deaths.iter() {
+		sound_evts.send(SoundClass::Sand);
+		sound_evts.send(SoundClass::Blocked);
+	}
+
+	let shown_now = if playback.push(report, delay) { 1 } else { report.subturns.len() };
+
+	for subturn in report.subturns.iter().take(shown_now) {
+		if let (MoveOutcome::Blocked, CharacterCommand::Move(dir)) = (subturn.outcome, subturn.command) {
+			bump_evts.send(BumpEvent {
+				character: subturn.character,
+				dir,
+			});
+		}
+	}
+}
 
-				turn.march_turn();
+fn char_sync(
+	sim: Res<ActiveSimulation>,
+	mut turn: ResMut<ActiveTurn>,
+	mut ghosts: ResMut<GhostLimit>,
+	mut query: Query<&mut Character>,
+) {
+	if let Some(sim) = &sim.0 {
+		for mut character in &mut query.iter() {
+			if let Some(state) = sim.characters().get(character.my_turn) {
+				*character = state.clone();
 			}
-		}	
+		}
+
+		*turn = sim.state().turn;
+		ghosts.0 = sim.state().ghosts_left;
 	}
 }
 
 fn char_display(
-	mut query: Query<(&Character, &mut DisplayGridPosition)>,
+	playback: Res<TurnPlayback>,
+	mut query: Query<(&Character, &mut DisplayGridPosition, &mut Draw)>,
 ) {
-	for (character, mut pos) in &mut query.iter() {
-		pos.0 = character.current;
+	for (character, mut pos, mut draw) in &mut query.iter() {
+		let shown = playback.position_of(character.my_turn);
+		pos.0 = shown.unwrap_or(character.current);
+
+		// Ghosts stay in sight until their fatal move has been shown.
+		draw.is_visible = !character.dead || shown.is_some();
 	}	
 }
 
@@ -237,28 +369,29 @@ fn char_set_collision(
 
 fn char_reset(
 	mut commands: Commands,
-	limit: Res<TurnLimit>,
-	mut ghosts: ResMut<GhostLimit>,
-	mut turn: ResMut<ActiveTurn>,
+	sim: Res<ActiveSimulation>,
 	mut meshes: ResMut<Assets<Mesh>>,
 	mut materials: ResMut<Assets<StandardMaterial>>,
 	asset_server: Res<AssetServer>,
 	mut textures: ResMut<Assets<Texture>>,
-	mut actives_query: Query<(Entity, &mut Character, &ActiveCharacter)>,
-	mut inactives_query: Query<(&mut Character, &InactiveCharacter)>,
+	mut actives_query: Query<(Entity, &Character, &ActiveCharacter)>,
 ) {
-	if turn.should_reset(limit.0) && ghosts.0 != 0 {
-		for (ent, mut character, _active_char) in &mut actives_query.iter() {
+	if let Some(sim) = &sim.0 {
+		for (ent, character, _active_char) in &mut actives_query.iter() {
+			if character.my_turn >= sim.active_index() {
+				continue;
+			}
+
 			commands.remove_one::<ActiveCharacter>(ent);
 			commands.insert_one(ent, InactiveCharacter);
 
-			let new = character.new_me();
+			let new = sim.active().clone();
 
 			new.spawn(&mut commands, &mut meshes, &mut materials, &asset_server, &mut textures);
 
 			let anim = EntAnim::Ghost;
 
-			let (material, anim) = match anim.handles(&asset_server, &mut textures, &mut materials) {
+			let (_material, anim) = match anim.handles(&asset_server, &mut textures, &mut materials) {
 				TexVariety::Unanim(mat) => (mat, None),
 				TexVariety::Anim(mat) => (mat.first().unwrap(), Some(mat)),
 			};
@@ -267,15 +400,6 @@ fn char_reset(
 				commands.remove_one::<AnimatedMaterial>(ent);
 				commands.insert_one(ent, anim);
 			}
-
-			character.reset();
-		}
-
-		for (mut character, mut _inactive) in &mut inactives_query.iter() {
-			character.reset();
 		}
-
-		turn.reset_and_add_ent();
-		ghosts.0 -= 1;
 	}
 }
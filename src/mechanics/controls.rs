@@ -0,0 +1,374 @@
+use bevy::app::stage;
+use bevy::input::gamepad::{
+	Gamepad,
+	GamepadAxis,
+	GamepadAxisType,
+	GamepadButton,
+	GamepadButtonType,
+	GamepadEvent,
+	GamepadEventType,
+};
+use bevy::prelude::*;
+use ron::de::from_reader;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs::File};
+
+pub const CONTROLS_LOCATION: &str = "assets/controls.ron";
+
+/// How many moves can be waiting for their turn at once, unless the config says otherwise.
+pub const DEFAULT_QUEUE_DEPTH: usize = 3;
+/// Seconds between each ghost's move, unless the config says otherwise.
+pub const DEFAULT_GHOST_DELAY: f32 = 0.2;
+
+/// How far the stick must be pushed before it counts as a move...
+const STICK_PRESS: f32 = 0.5;
+/// ...and how far it must come back before another push counts.
+const STICK_RELEASE: f32 = 0.3;
+
+/// Everything the player can ask for, independent of which key (or button) asks for it.
+///
+/// Systems read these through `Input<Action>`, exactly as they would `Input<KeyCode>`.
+/// The level editor's painting keys are the exception, and stay fixed.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Action {
+	MoveNorth,
+	MoveEast,
+	MoveSouth,
+	MoveWest,
+	Wait,
+	FastForward,
+	Undo,
+	Rewind,
+	Restart,
+	CameraLeft,
+	CameraRight,
+	CameraUp,
+	TogglePaths,
+	ToggleFps,
+	Menu,
+	Confirm,
+	ToggleEditor,
+	DebugStart,
+	DebugRestart,
+	DebugExit,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Preset {
+	/// Arrow keys to move, as in the original jam build.
+	Arrows,
+	/// WASD to move; the debug keys move to F6-F8.
+	Wasd,
+	/// hjkl to move.
+	Vim,
+}
+
+impl Default for Preset {
+	fn default() -> Self {
+		Preset::Arrows
+	}
+}
+
+/// The contents of `assets/controls.ron`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+	pub preset: Preset,
+	/// Replaces the preset's keys for each action listed, e.g. `Wait: ["Space", "Period"]`.
+	pub bindings: HashMap<Action, Vec<String>>,
+	/// Moves pressed before the character can take them are kept, up to this many.
+	pub queue_depth: usize,
+	/// Seconds between each ghost's move; 0 shows them all at once.
+	pub ghost_delay: f32,
+}
+
+impl Default for ControlsConfig {
+	fn default() -> Self {
+		Self {
+			preset: Preset::default(),
+			bindings: HashMap::new(),
+			queue_depth: DEFAULT_QUEUE_DEPTH,
+			ghost_delay: DEFAULT_GHOST_DELAY,
+		}
+	}
+}
+
+/// Which keys (and gamepad buttons) trigger each action.
+#[derive(Clone, Debug)]
+pub struct InputMap {
+	bindings: HashMap<Action, Vec<KeyCode>>,
+	buttons: HashMap<Action, Vec<GamepadButtonType>>,
+	pub queue_depth: usize,
+	pub ghost_delay: f32,
+}
+
+impl Default for InputMap {
+	fn default() -> Self {
+		Self::preset(Preset::default())
+	}
+}
+
+impl InputMap {
+	pub fn preset(preset: Preset) -> Self {
+		use Action::*;
+		use KeyCode as K;
+
+		let mut bindings: HashMap<Action, Vec<KeyCode>> = [
+			(MoveNorth, vec![K::Up]),
+			(MoveEast, vec![K::Right]),
+			(MoveSouth, vec![K::Down]),
+			(MoveWest, vec![K::Left]),
+			(Wait, vec![K::Space]),
+			(FastForward, vec![K::F]),
+			(Undo, vec![K::U]),
+			(Rewind, vec![K::R]),
+			(Restart, vec![K::Back]),
+			(CameraLeft, vec![K::Z]),
+			(CameraRight, vec![K::C]),
+			(CameraUp, vec![K::X]),
+			(TogglePaths, vec![K::P]),
+			(ToggleFps, vec![K::F1]),
+			(Menu, vec![K::Escape]),
+			(Confirm, vec![K::Return]),
+			(ToggleEditor, vec![K::F2]),
+			(DebugStart, vec![K::Q]),
+			(DebugRestart, vec![K::W]),
+			(DebugExit, vec![K::E]),
+		].iter().cloned().collect();
+
+		let movement = match preset {
+			Preset::Arrows => vec![],
+			Preset::Wasd => vec![
+				(MoveNorth, K::W),
+				(MoveEast, K::D),
+				(MoveSouth, K::S),
+				(MoveWest, K::A),
+			],
+			Preset::Vim => vec![
+				(MoveNorth, K::K),
+				(MoveEast, K::L),
+				(MoveSouth, K::J),
+				(MoveWest, K::H),
+			],
+		};
+
+		for (action, key) in movement {
+			bindings.entry(action)
+				.or_default()
+				.push(key);
+		}
+
+		if preset == Preset::Wasd {
+			bindings.insert(DebugStart, vec![K::F6]);
+			bindings.insert(DebugRestart, vec![K::F7]);
+			bindings.insert(DebugExit, vec![K::F8]);
+		}
+
+		Self {
+			bindings,
+			buttons: Self::default_buttons(),
+			queue_depth: DEFAULT_QUEUE_DEPTH,
+			ghost_delay: DEFAULT_GHOST_DELAY,
+		}
+	}
+
+	/// The left stick also moves; see `stick_direction`.
+	fn default_buttons() -> HashMap<Action, Vec<GamepadButtonType>> {
+		use Action::*;
+		use GamepadButtonType as B;
+
+		[
+			(MoveNorth, vec![B::DPadUp]),
+			(MoveEast, vec![B::DPadRight]),
+			(MoveSouth, vec![B::DPadDown]),
+			(MoveWest, vec![B::DPadLeft]),
+			(Wait, vec![B::South]),
+			(Confirm, vec![B::South]),
+			(FastForward, vec![B::Select]),
+			(Undo, vec![B::West]),
+			(Rewind, vec![B::East]),
+			(Restart, vec![B::North]),
+			(CameraLeft, vec![B::LeftTrigger]),
+			(CameraRight, vec![B::RightTrigger]),
+			(CameraUp, vec![B::LeftTrigger2, B::RightTrigger2]),
+			(TogglePaths, vec![B::RightThumb]),
+			(Menu, vec![B::Start]),
+		].iter().cloned().collect()
+	}
+
+	/// Builds the map described by a config, reporting (and skipping) any unknown key names.
+	pub fn from_config(config: &ControlsConfig) -> (Self, Vec<String>) {
+		let mut out = Self::preset(config.preset);
+		let mut unknown = vec![];
+
+		out.queue_depth = config.queue_depth;
+		out.ghost_delay = config.ghost_delay;
+
+		for (action, names) in config.bindings.iter() {
+			let keys = names.iter()
+				.filter_map(|name| {
+					let key = key_from_name(name);
+					if key.is_none() {
+						unknown.push(format!("{:?}: unknown key {:?}", action, name));
+					}
+					key
+				})
+				.collect();
+
+			out.bindings.insert(*action, keys);
+		}
+
+		(out, unknown)
+	}
+
+	/// Reads the controls config, falling back to the default preset if there isn't a usable one.
+	pub fn load(path: &str) -> Self {
+		let config = match File::open(path) {
+			Ok(f) => match from_reader(f) {
+				Ok(config) => config,
+				Err(e) => {
+					eprintln!("{}: could not be parsed: {}", path, e);
+					ControlsConfig::default()
+				},
+			},
+			Err(_) => ControlsConfig::default(),
+		};
+
+		let (out, unknown) = Self::from_config(&config);
+		for problem in unknown {
+			eprintln!("{}: {}", path, problem);
+		}
+
+		out
+	}
+
+	pub fn keys(&self, action: Action) -> &[KeyCode] {
+		self.bindings.get(&action)
+			.map(|keys| &keys[..])
+			.unwrap_or(&[])
+	}
+
+	/// What to call the first key bound to an action in on-screen hints.
+	pub fn key_name(&self, action: Action) -> String {
+		match self.keys(action).first() {
+			Some(KeyCode::Back) => "Backspace".into(),
+			Some(KeyCode::Return) => "Enter".into(),
+			Some(key) => format!("{:?}", key),
+			None => format!("{:?} (unbound)", action),
+		}
+	}
+}
+
+macro_rules! key_names {
+	($($key:ident),* $(,)?) => {
+		fn key_from_name(name: &str) -> Option<KeyCode> {
+			match name {
+				$(stringify!($key) => Some(KeyCode::$key),)*
+				_ => None,
+			}
+		}
+	};
+}
+
+key_names!(
+	Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+	A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+	F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+	Up, Down, Left, Right,
+	Escape, Back, Return, Space, Tab,
+	Insert, Delete, Home, End, PageUp, PageDown,
+	LShift, RShift, LControl, RControl, LAlt, RAlt,
+	Apostrophe, Backslash, Comma, Equals, Grave, LBracket, Minus, Period, RBracket, Semicolon, Slash,
+	Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+);
+
+/// Connected gamepads, and which way each one's left stick is being held.
+#[derive(Default)]
+pub struct Gamepads {
+	sticks: HashMap<Gamepad, Option<Action>>,
+	reader: EventReader<GamepadEvent>,
+}
+
+/// Reads a stick as a single move, with a dead zone and some hysteresis so that
+/// one push is one move.
+fn stick_direction(x: f32, y: f32, held: Option<Action>) -> Option<Action> {
+	let magnitude = x.abs().max(y.abs());
+
+	if magnitude < STICK_RELEASE {
+		None
+	} else if magnitude < STICK_PRESS {
+		held
+	} else if x.abs() > y.abs() {
+		Some(if x > 0.0 { Action::MoveEast } else { Action::MoveWest })
+	} else {
+		Some(if y > 0.0 { Action::MoveNorth } else { Action::MoveSouth })
+	}
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_resource(InputMap::default())
+			.add_resource(Gamepads::default())
+			.add_resource(Input::<Action>::default())
+			.add_system_to_stage(stage::PRE_UPDATE, action_update.system());
+	}
+}
+
+/// Turns this frame's key presses (and gamepad input) into actions.
+fn action_update(
+	map: Res<InputMap>,
+	keys: Res<Input<KeyCode>>,
+	mut pads: ResMut<Gamepads>,
+	pad_evts: Res<Events<GamepadEvent>>,
+	buttons: Res<Input<GamepadButton>>,
+	axes: Res<Axis<GamepadAxis>>,
+	mut actions: ResMut<Input<Action>>,
+) {
+	let pads = &mut *pads;
+	for evt in pads.reader.iter(&pad_evts) {
+		match evt {
+			GamepadEvent(pad, GamepadEventType::Connected) => {
+				pads.sticks.insert(*pad, None);
+			},
+			GamepadEvent(pad, GamepadEventType::Disconnected) => {
+				pads.sticks.remove(pad);
+			},
+			_ => {},
+		}
+	}
+
+	for (pad, held) in pads.sticks.iter_mut() {
+		let x = axes.get(&GamepadAxis(*pad, GamepadAxisType::LeftStickX)).unwrap_or_default();
+		let y = axes.get(&GamepadAxis(*pad, GamepadAxisType::LeftStickY)).unwrap_or_default();
+		*held = stick_direction(x, y, *held);
+	}
+
+	actions.update();
+
+	let no_buttons = vec![];
+	for (action, bound) in map.bindings.iter() {
+		let bound_buttons = map.buttons.get(action).unwrap_or(&no_buttons);
+		let pad_buttons = || pads.sticks.keys()
+			.flat_map(move |pad| bound_buttons.iter().map(move |button| GamepadButton(*pad, *button)));
+
+		// A tap can start and end within one frame, so catch it separately.
+		let tapped = bound.iter().any(|key| keys.just_pressed(*key))
+			|| pad_buttons().any(|button| buttons.just_pressed(button));
+
+		let held = bound.iter().any(|key| keys.pressed(*key))
+			|| pad_buttons().any(|button| buttons.pressed(button))
+			|| pads.sticks.values().any(|stick| *stick == Some(*action));
+
+		if tapped {
+			actions.press(*action);
+		}
+
+		if held {
+			actions.press(*action);
+		} else if actions.pressed(*action) {
+			actions.release(*action);
+		}
+	}
+}
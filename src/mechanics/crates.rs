@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+use super::{ActiveSimulation, CollideGridPosition, DisplayGridPosition};
+
+/// The `n`th crate listed in the map, drawn wherever the simulation has it now.
+pub struct CrateBody(pub usize);
+
+pub struct CratePlugin;
+
+impl Plugin for CratePlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_system(crate_sync.system());
+	}
+}
+
+fn crate_sync(
+	sim: Res<ActiveSimulation>,
+	mut query: Query<(&CrateBody, &mut DisplayGridPosition, &mut CollideGridPosition)>,
+) {
+	if let Some(sim) = &sim.0 {
+		for (body, mut display, mut collide) in &mut query.iter() {
+			if let Some(pos) = sim.state().crates.get(body.0) {
+				display.0 = *pos;
+				collide.0 = *pos;
+			}
+		}
+	}
+}
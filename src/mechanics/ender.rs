@@ -1,13 +1,16 @@
 use bevy::prelude::*;
 
-use crate::map::meta::Levels;
+use crate::map::meta::{LevelOverride, Levels, ManifestPath};
+use crate::map::save::SaveData;
 
+use super::ActiveSimulation;
 use super::Alive;
-use super::DisplayGridPosition;
-use super::CollideGridPosition;
-use super::character::Character;
+use super::controls::Action;
 use super::events::LevelExit;
 use super::events::Restart;
+use super::playback::TurnPlayback;
+use super::replay::{ActiveReplay, Replay};
+use super::simulation::SimStatus;
 
 #[derive(Debug, Default,)]
 pub struct Ender{ fired: bool }
@@ -23,40 +26,75 @@ impl Plugin for EnderPlugin {
 
 fn ender_progresses_level(
 	mut level_info: ResMut<Levels>,
+	level_override: Res<LevelOverride>,
+	manifest_path: Res<ManifestPath>,
+	mut save: ResMut<SaveData>,
+	replay: Res<ActiveReplay>,
 	mut exits: ResMut<Events<LevelExit>>,
-	mut query: Query<(&mut Ender, &DisplayGridPosition)>,
-	mut chars_query: Query<(&Character, &CollideGridPosition)>,
-	mut ents_query: Query<&mut Alive>,
+	sim: Res<ActiveSimulation>,
+	playback: Res<TurnPlayback>,
+	mut query: Query<&mut Ender>,
 ) {
-	let mut do_end = None;
-	for (mut ender, pos) in &mut query.iter() {
-		if ender.fired {
-			continue;
+	let sim = match &sim.0 {
+		Some(sim) if sim.status() == SimStatus::Won => sim,
+		_ => return,
+	};
+
+	// Show the winning turn before leaving.
+	if !playback.is_idle() {
+		return;
+	}
+
+	let mut do_end = false;
+	for mut ender in &mut query.iter() {
+		if !ender.fired {
+			ender.fired = true;
+			do_end = true;
 		}
+	}
+
+	if do_end {
+		// An edited level isn't the one on disk, and a replay isn't the player's
+		// own win, so neither is kept.
+		let edited = level_override.is_edited(level_info.start_at);
+		let watched = replay.0.is_some();
+		let outside = level_info.data.get(level_info.start_at).is_none();
 
-		for (_char, char_pos) in &mut chars_query.iter() {
-			if char_pos.0 == pos.0 {
-				do_end = Some(pos.0.clone());
-				ender.fired = true;
+		// A level from outside the manifest is known by the file it was read from.
+		let path = level_info.data.get(level_info.start_at)
+			.map(|level| level.path.as_str())
+			.or_else(|| level_override.path_for(level_info.start_at));
+
+		if let Some(path) = path.filter(|_| !edited && !watched) {
+			if !outside && manifest_path.is_default() {
+				save.record(path, sim.turns_taken(), sim.characters().len());
+			}
+
+			let replay = Replay::from_simulation(path, sim);
+			if let Some(path) = replay.new_location() {
+				if let Err(e) = replay.write(&path) {
+					eprintln!("Could not save replay: {}", e);
+				}
 			}
 		}
-	}
 
-	if let Some(_end_pos) = do_end {
 		//despawn all
 		exits.send(LevelExit);
 
 		// increment map.
 		level_info.load_next();
+		if !watched && !edited && !outside && manifest_path.is_default() {
+			save.current = level_info.start_at;
+		}
 	}
 }
 
 fn restart_button(
-	inputs: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
 	mut res: ResMut<Events<Restart>>,
 	mut ents_query: Query<&mut Alive>,
 ) {
-	if inputs.just_pressed(KeyCode::Back) {
+	if actions.just_pressed(Action::Restart) {
 		res.send(Restart);
 		// trigger_restart(&mut ents_query);
 	}
@@ -1,10 +1,14 @@
 use bevy::prelude::*;
 use numerals::roman::Roman;
 
-use crate::map::meta::Levels;
+use crate::map::meta::{LevelFailure, Levels, ManifestPath};
+use crate::map::save::SaveData;
 
 use super::Alive;
+use super::controls::Action;
 use super::camera::CameraMode;
+use super::character::InputQueue;
+use super::playback::TurnPlayback;
 use super::audio::StepEvent;
 use super::ender::trigger_restart;
 
@@ -17,6 +21,7 @@ impl Plugin for EventPlugin {
 			.add_event::<LevelExit>()
 			.add_event::<DoLevelGen>()
 			.add_event::<SpawnLevelText>()
+			.add_resource(DebugKeys::default())
 			.add_system(handle_restart.system())
 			.add_system(handle_start.system())
 			.add_system(handle_exit.system())
@@ -61,14 +66,39 @@ fn handle_start(
 pub struct DoLevelGen;
 
 fn handle_dolevelgen(
-	levels: Res<Levels>,
+	mut levels: ResMut<Levels>,
+	mut failure: ResMut<LevelFailure>,
+	manifest_path: Res<ManifestPath>,
+	save: Res<SaveData>,
 	evts: Res<Events<DoLevelGen>>,
+	mut queue: ResMut<InputQueue>,
+	mut playback: ResMut<TurnPlayback>,
 	mut textevts: ResMut<Events<SpawnLevelText>>,
 	mut ents_query: Query<&mut Alive>,
 ) {
 	for _evt in evts.get_reader().iter(&evts) {
 		trigger_restart(&mut ents_query);
 
+		// Moves pressed for the old level mean nothing in the new one.
+		queue.clear();
+		playback.clear();
+
+		// Give a broken level (or manifest) another try, in case it has since been fixed.
+		if failure.0.take().is_some() && levels.data.is_empty() {
+			match Levels::from_path(&manifest_path.0) {
+				Ok(fresh) => {
+					*levels = fresh;
+					if manifest_path.is_default() {
+						levels.start_at = save.current.min(levels.data.len());
+					}
+				},
+				Err(e) => {
+					failure.0 = Some(e);
+					continue;
+				},
+			}
+		}
+
 		if let Some(level) = levels.data.get(levels.start_at) {
 			let romanify: Roman = (levels.start_at as i16 + 1).into();
 			textevts.send(SpawnLevelText(format!("{:X}: {}", romanify, level.name)));
@@ -80,21 +110,30 @@ fn handle_dolevelgen(
 
 pub struct SpawnLevelText(pub String);
 
+/// Whether the debug actions (Q, W, and E by default) fire level events directly, for testing transitions.
+#[derive(Debug, Default)]
+pub struct DebugKeys(pub bool);
+
 fn debug_sender(
+	enabled: Res<DebugKeys>,
 	mut sta: ResMut<Events<LevelStart>>,
 	mut res: ResMut<Events<Restart>>,
 	mut exits: ResMut<Events<LevelExit>>,
-	input: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
 ) {
-	if input.just_pressed(KeyCode::Q) {
+	if !enabled.0 {
+		return;
+	}
+
+	if actions.just_pressed(Action::DebugStart) {
 		sta.send(LevelStart);
 	}
 
-	if input.just_pressed(KeyCode::W) {
+	if actions.just_pressed(Action::DebugRestart) {
 		res.send(Restart);
 	}
 
-	if input.just_pressed(KeyCode::E) {
+	if actions.just_pressed(Action::DebugExit) {
 		exits.send(LevelExit);
 	}
 }
@@ -3,9 +3,18 @@ pub mod buttons;
 pub mod camera;
 pub mod character;
 pub mod constants;
+pub mod controls;
+pub mod crates;
 pub mod ender;
 pub mod events;
+pub mod motion;
+pub mod playback;
+pub mod pointer;
+pub mod replay;
+pub mod simulation;
+pub mod solver;
 pub mod spawner;
+pub mod switches;
 
 use bevy::prelude::*;
 use enum_primitive::*;
@@ -13,11 +22,10 @@ use enum_primitive::*;
 use camera::CameraPlugin;
 use character::CharacterCommand;
 use crate::map::Map;
-use crate::map::WORLD_HEIGHT_SCALE;
 use serde::{Deserialize, Serialize};
 
 enum_from_primitive!{
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub enum Direction {
 	North,
 	East,
@@ -32,6 +40,13 @@ impl Direction {
 	}
 }
 
+pub const DIRECTIONS: [Direction; 4] = [
+	Direction::North,
+	Direction::East,
+	Direction::South,
+	Direction::West,
+];
+
 impl Default for Direction {
 	fn default() -> Self {
 	    Direction::North
@@ -41,12 +56,18 @@ impl Default for Direction {
 pub type Ordinate = isize;
 
 // height is a derived property
-#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct GridPosition {
 	pub x: Ordinate,
 	pub y: Ordinate,
 }
 
+impl std::fmt::Display for GridPosition {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "({}, {})", self.x, self.y)
+	}
+}
+
 pub struct DisplayGridPosition(pub GridPosition);
 pub struct CollideGridPosition(pub GridPosition);
 
@@ -126,18 +147,21 @@ fn camera_facer(
 #[derive(Debug, Default)]
 pub struct OccupationMap(pub Vec<bool>);
 
-impl OccupationMap {
-	pub fn move_collider(&mut self, from: usize, to: usize) {
-		self.0[from] = false;
-		self.0[to] = true;
-	}
-}
+/// Set while another mode (e.g., the level editor) owns the arrow keys and camera.
+#[derive(Debug, Default)]
+pub struct ControlsLocked(pub bool);
+
+/// Rules for the level currently in play, if one exists.
+#[derive(Debug, Default)]
+pub struct ActiveSimulation(pub Option<simulation::Simulation>);
 
 pub struct RenderPlugin;
 
 impl Plugin for RenderPlugin {
 	fn build(&self, app: &mut AppBuilder) {
-		app.add_system(display_pos_to_world.system());
+		app.add_event::<motion::BumpEvent>()
+			.add_system(display_pos_to_world.system())
+			.add_system(motion::motion_bump.system());
 	}
 }
 
@@ -146,28 +170,33 @@ pub struct MechanicsPlugin;
 impl Plugin for MechanicsPlugin {
 	fn build(&self, app: &mut AppBuilder) {
 		app.add_plugin(RenderPlugin)
+			.add_plugin(controls::ControlsPlugin)
 			.add_plugin(events::EventPlugin)
 			.add_plugin(audio::AudioPlugin)
 			.add_plugin(CameraPlugin)
-			.add_resource(OccupationMap::default())
-			.add_system(collision_populater.system())
+			.add_resource(ActiveSimulation::default())
+			.add_resource(ControlsLocked::default())
 			.add_system(despawn_if_not_alive.system())
 			.add_plugin(character::CharacterPlugin)
 			.add_plugin(spawner::SpawnerPlugin)
 			.add_plugin(ender::EnderPlugin)
 			.add_plugin(buttons::ButtonPlugin)
+			.add_plugin(crates::CratePlugin)
+			.add_plugin(switches::SwitchPlugin)
+			.add_plugin(playback::PlaybackPlugin)
+			.add_plugin(pointer::PointerPlugin)
+			.add_plugin(replay::ReplayPlugin)
 			.add_system(camera_facer.system())
 			.add_resource(TurnLimit(1))
 			.add_resource(GhostLimit(1))
-			.add_resource(ActiveTurn::default())
-			.add_system(turn_lockout.system());
+			.add_resource(ActiveTurn::default());
 	}
 }
 
-#[derive(Clone, Copy, Debug, Default, Properties, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Properties, Deserialize, PartialEq, Serialize)]
 pub struct TurnLimit(pub usize);
 
-#[derive(Clone, Copy, Debug, Default, Properties, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Properties, Deserialize, PartialEq, Serialize)]
 pub struct GhostLimit(pub usize);
 
 #[derive(Clone, Copy, Debug, Default, Properties)]
@@ -175,18 +204,9 @@ pub struct ActiveTurn {
 	active_ent_refresh: usize,
 	active_ent: usize,
 	pub turn: usize,
-	block_turn: bool,
 }
 
 impl ActiveTurn {
-	pub fn should_reset(&self, limit: usize) -> bool {
-		self.turn == limit
-	}
-
-	pub fn allow_turn(&self, limit: usize, my_subturn: usize) -> bool {
-		!self.block_turn && self.turn != limit && my_subturn == self.active_ent
-	}
-
 	pub fn march_turn(&mut self) {
 		if self.active_ent == 0 {
 			self.active_ent = self.active_ent_refresh;
@@ -194,8 +214,11 @@ impl ActiveTurn {
 		} else {
 			self.active_ent -= 1;
 		}
+	}
 
-		self.block_turn = false;
+	pub fn restart_loop(&mut self) {
+		self.active_ent = self.active_ent_refresh;
+		self.turn = 0;
 	}
 
 	pub fn reset_and_add_ent(&mut self) {
@@ -209,43 +232,27 @@ impl ActiveTurn {
 	}
 }
 
-fn turn_lockout(
-	mut turn: ResMut<ActiveTurn>,
-) {
-	turn.block_turn = false;
-}
-
-fn collision_populater(
-	mut occupation: ResMut<OccupationMap>,
-	mut map_query: Query<&Map>,
-	mut query: Query<&CollideGridPosition>,
-) {
-	for map in &mut map_query.iter() {
-		let o_len = occupation.0.len();
-		occupation.0.resize(map.len(), false);
-		occupation.0[..o_len].iter_mut()
-			.map(|x| *x = false)
-			.count();
-
-		for pos in &mut query.iter() {
-			occupation.0[pos.0.unroll(map.width) as usize] = true;
-		}
-	}
-}
-
 fn display_pos_to_world(
+	time: Res<Time>,
 	mut map_query: Query<&Map>,
-	mut query: Query<(&DisplayGridPosition, &mut Transform)>,
+	mut query: Query<(&DisplayGridPosition, &mut Transform, Option<&mut motion::Motion>)>,
 ) {
 	for map in &mut map_query.iter() {
-		for (pos, mut transform) in &mut query.iter() {
+		for (pos, mut transform, motion) in &mut query.iter() {
 			let pos = pos.0;
-			let height = map.heights[pos.unroll(map.width) as usize];
-			transform.set_translation(Vec3::new(
-				-pos.y as f32,
-				(height as f32) * WORLD_HEIGHT_SCALE + 0.5,
-				pos.x as f32,
-			));
+			let target = match motion::cell_to_world(map, pos) {
+				Some(target) => target,
+				None => continue,
+			};
+
+			match motion {
+				Some(mut motion) => {
+					motion::advance(map, pos, target, &mut motion, &mut transform, time.delta_seconds);
+				},
+				None => {
+					transform.set_translation(target);
+				},
+			}
 		}
 	}
 }
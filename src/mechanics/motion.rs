@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use enum_primitive::*;
+
+use crate::map::{Map, TileShape, WORLD_HEIGHT_SCALE};
+
+use super::character::Character;
+use super::{Direction, GridPosition};
+
+const MOVE_SECS: f32 = 0.15;
+const BUMP_SECS: f32 = 0.15;
+/// Peak of the arc when stepping up or down a level...
+const HOP_HEIGHT: f32 = 0.35;
+/// ...which needn't be as high when walking along a slope.
+const SLOPE_HOP_HEIGHT: f32 = 0.15;
+/// How far into a blocked cell a character leans before springing back.
+const BUMP_DISTANCE: f32 = 0.2;
+
+/// A character failed to move in this direction.
+pub struct BumpEvent {
+	/// Index of the character, as in `SubTurn::character`.
+	pub character: usize,
+	pub dir: Direction,
+}
+
+/// Eases an entity between cells instead of snapping it there.
+///
+/// Only entities with this component are tweened; everything else placed by
+/// `DisplayGridPosition` still snaps.
+#[derive(Debug, Default)]
+pub struct Motion {
+	cell: Option<GridPosition>,
+	from: Vec3,
+	to: Vec3,
+	hop: f32,
+	bump: Vec3,
+	elapsed: f32,
+	duration: f32,
+}
+
+impl Motion {
+	fn finished(&self) -> bool {
+		self.elapsed >= self.duration
+	}
+
+	fn start(&mut self, from: Vec3, to: Vec3, duration: f32) {
+		self.from = from;
+		self.to = to;
+		self.hop = 0.0;
+		self.bump = Vec3::zero();
+		self.elapsed = 0.0;
+		self.duration = duration;
+	}
+
+	fn current(&self) -> Vec3 {
+		if self.finished() {
+			return self.to;
+		}
+
+		let t = self.elapsed / self.duration;
+		let eased = t * t * (3.0 - 2.0 * t);
+		let arc = (t * std::f32::consts::PI).sin();
+
+		self.from + (self.to - self.from) * eased
+			+ Vec3::new(0.0, self.hop * arc, 0.0)
+			+ self.bump * arc
+	}
+}
+
+pub fn cell_to_world(map: &Map, pos: GridPosition) -> Option<Vec3> {
+	map.heights.get(pos.unroll(map.width) as usize)
+		.map(|height| Vec3::new(
+			-pos.y as f32,
+			(*height as f32) * WORLD_HEIGHT_SCALE + 0.5,
+			pos.x as f32,
+		))
+}
+
+pub fn direction_to_world(dir: Direction) -> Vec3 {
+	let step = GridPosition::default().neighbour(dir);
+	Vec3::new(-step.y as f32, 0.0, step.x as f32)
+}
+
+fn hop_height(map: &Map, from: GridPosition, to: GridPosition) -> f32 {
+	let index = |pos: GridPosition| pos.unroll(map.width) as usize;
+
+	if map.heights.get(index(from)) == map.heights.get(index(to)) {
+		return 0.0;
+	}
+
+	let on_slope = [from, to].iter()
+		.any(|pos| map.tile_shapes.get(index(*pos))
+			.and_then(|shape| TileShape::from_u8(*shape))
+			== Some(TileShape::Slope)
+		);
+
+	if on_slope {
+		SLOPE_HOP_HEIGHT
+	} else {
+		HOP_HEIGHT
+	}
+}
+
+fn is_neighbour(a: GridPosition, b: GridPosition) -> bool {
+	(a.x - b.x).abs() + (a.y - b.y).abs() == 1
+}
+
+/// Moves a tweened entity towards its cell, starting a new tween whenever the cell changes.
+pub fn advance(
+	map: &Map,
+	pos: GridPosition,
+	target: Vec3,
+	motion: &mut Motion,
+	transform: &mut Transform,
+	delta: f32,
+) {
+	match motion.cell {
+		// Loop resets, undos and fresh spawns jump straight there.
+		Some(cell) if cell != pos && is_neighbour(cell, pos) => {
+			let hop = hop_height(map, cell, pos);
+			motion.start(transform.translation(), target, MOVE_SECS);
+			motion.hop = hop;
+		},
+		Some(cell) if cell == pos && motion.to == target => {},
+		_ => {
+			motion.start(target, target, 0.0);
+		},
+	}
+
+	motion.cell = Some(pos);
+	motion.elapsed = (motion.elapsed + delta).min(motion.duration);
+
+	transform.set_translation(motion.current());
+}
+
+pub fn motion_bump(
+	mut reader: Local<EventReader<BumpEvent>>,
+	evts: Res<Events<BumpEvent>>,
+	mut query: Query<(&Character, &mut Motion)>,
+) {
+	for evt in reader.iter(&evts) {
+		for (character, mut motion) in &mut query.iter() {
+			if character.my_turn != evt.character || !motion.finished() {
+				continue;
+			}
+
+			let rest = motion.to;
+			motion.start(rest, rest, BUMP_SECS);
+			motion.bump = direction_to_world(evt.dir) * BUMP_DISTANCE;
+		}
+	}
+}
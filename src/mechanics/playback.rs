@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use super::character::CharacterCommand;
+use super::controls::Action;
+use super::motion::BumpEvent;
+use super::simulation::{MoveOutcome, SubTurn, TurnReport};
+use super::GridPosition;
+
+/// Holding fast-forward speeds ghost playback up by this much.
+const FAST_FORWARD_RATE: f32 = 6.0;
+
+/// Ghost subturns which have already happened in the simulation, but which
+/// haven't been shown yet.
+///
+/// The simulation resolves a whole turn at once; this lets each ghost's move
+/// be seen in order after the player's own.
+#[derive(Debug)]
+pub struct TurnPlayback {
+	pending: VecDeque<SubTurn>,
+	showing: Option<SubTurn>,
+	timer: Timer,
+}
+
+impl Default for TurnPlayback {
+	fn default() -> Self {
+		Self {
+			pending: VecDeque::new(),
+			showing: None,
+			timer: Timer::from_seconds(1.0, true),
+		}
+	}
+}
+
+impl TurnPlayback {
+	/// Queues the ghosts' part of a turn; the active character's is shown straight away.
+	///
+	/// Returns false if the ghosts should be shown at once instead.
+	pub fn push(&mut self, report: &TurnReport, delay: f32) -> bool {
+		// After a loop everyone is back at the start, so there's nothing to show them doing.
+		if delay <= 0.0 || report.looped {
+			return false;
+		}
+
+		if self.is_idle() {
+			self.timer = Timer::from_seconds(delay, true);
+		}
+
+		self.pending.extend(report.subturns.iter().skip(1));
+		true
+	}
+
+	pub fn clear(&mut self) {
+		self.pending.clear();
+		self.showing = None;
+	}
+
+	pub fn is_idle(&self) -> bool {
+		self.pending.is_empty() && self.showing.is_none()
+	}
+
+	/// Where a character should be drawn, if it differs from where the simulation has it.
+	pub fn position_of(&self, character: usize) -> Option<GridPosition> {
+		if let Some(subturn) = self.showing.filter(|subturn| subturn.character == character) {
+			return Some(match subturn.outcome {
+				MoveOutcome::Moved(pos) => pos,
+				_ => subturn.from,
+			});
+		}
+
+		self.pending.iter()
+			.find(|subturn| subturn.character == character)
+			.map(|subturn| subturn.from)
+	}
+}
+
+pub struct PlaybackPlugin;
+
+impl Plugin for PlaybackPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_resource(TurnPlayback::default())
+			.add_system(playback_advance.system());
+	}
+}
+
+fn playback_advance(
+	time: Res<Time>,
+	actions: Res<Input<Action>>,
+	mut playback: ResMut<TurnPlayback>,
+	mut bump_evts: ResMut<Events<BumpEvent>>,
+) {
+	if playback.is_idle() {
+		return;
+	}
+
+	let rate = if actions.pressed(Action::FastForward) {
+		FAST_FORWARD_RATE
+	} else {
+		1.0
+	};
+
+	playback.timer.tick(time.delta_seconds * rate);
+	if !playback.timer.just_finished {
+		return;
+	}
+
+	let next = playback.pending.pop_front();
+	if let Some(SubTurn { character, command: CharacterCommand::Move(dir), outcome: MoveOutcome::Blocked, .. }) = next {
+		bump_evts.send(BumpEvent {
+			character,
+			dir,
+		});
+	}
+
+	playback.showing = next;
+}
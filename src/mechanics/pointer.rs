@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use crate::map::{EntShape, Map, TileHeight, WORLD_HEIGHT_SCALE};
+
+use super::character::InputQueue;
+use super::{ActiveSimulation, CameraFaced, ControlsLocked, DisplayGridPosition, GridPosition, Ordinate};
+
+const HIGHLIGHT_COLOUR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+
+/// Where the mouse is, and the route the active character would take to get there.
+pub struct Pointer {
+	reader: EventReader<CursorMoved>,
+	cursor: Option<Vec2>,
+	path: Vec<GridPosition>,
+	/// The route as last drawn.
+	shown: Vec<GridPosition>,
+	material: Handle<StandardMaterial>,
+}
+
+/// Marks one cell of the route under the mouse.
+pub struct PathHighlight;
+
+pub struct PointerPlugin;
+
+impl Plugin for PointerPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_startup_system(setup.system())
+			.add_system(pointer_path.system())
+			.add_system(pointer_highlight.system());
+	}
+}
+
+fn setup(
+	mut commands: Commands,
+	mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+	let material = materials.add(StandardMaterial {
+		albedo: HIGHLIGHT_COLOUR,
+		shaded: false,
+		..Default::default()
+	});
+
+	commands.insert_resource(Pointer {
+		reader: Default::default(),
+		cursor: None,
+		path: vec![],
+		shown: vec![],
+		material,
+	});
+}
+
+/// Finds the cell under a point on the screen (in pixels from the bottom left).
+///
+/// The camera is orthographic, with its view already scaled to pixels, so every
+/// ray runs parallel to its forward axis.
+pub fn pick_cell(map: &Map, camera: &Transform, window: &Window, cursor: Vec2) -> Option<GridPosition> {
+	let from_centre = Vec3::new(
+		cursor.x() - window.width as f32 * 0.5,
+		cursor.y() - window.height as f32 * 0.5,
+		0.0,
+	);
+
+	let origin = camera.value().transform_point3(from_centre);
+	let dir = camera.value().transform_vector3(Vec3::new(0.0, 0.0, -1.0)).normalize();
+
+	if dir.y().abs() < std::f32::consts::EPSILON {
+		return None;
+	}
+
+	let mut best: Option<(f32, GridPosition)> = None;
+
+	for (i, height) in map.heights.iter().enumerate() {
+		let pos = GridPosition {
+			x: i as Ordinate % map.width,
+			y: i as Ordinate / map.width,
+		};
+
+		let surface = TileHeight::from(*height).to_raw_height() as f32 * WORLD_HEIGHT_SCALE;
+		let t = (surface - origin.y()) / dir.y();
+		if t <= 0.0 || best.map(|(best_t, _)| t >= best_t).unwrap_or(false) {
+			continue;
+		}
+
+		let hit = origin + dir * t;
+		if (hit.x() + pos.y as f32).abs() <= 0.5 && (hit.z() - pos.x as f32).abs() <= 0.5 {
+			best = Some((t, pos));
+		}
+	}
+
+	best.map(|(_, pos)| pos)
+}
+
+fn pointer_path(
+	windows: Res<Windows>,
+	cursor_evts: Res<Events<CursorMoved>>,
+	buttons: Res<Input<MouseButton>>,
+	locked: Res<ControlsLocked>,
+	sim: Res<ActiveSimulation>,
+	mut pointer: ResMut<Pointer>,
+	mut queue: ResMut<InputQueue>,
+	mut cameras: Query<(&CameraFaced, &Transform)>,
+) {
+	let pointer = &mut *pointer;
+	for evt in pointer.reader.iter(&cursor_evts) {
+		pointer.cursor = Some(evt.position);
+	}
+
+	pointer.path.clear();
+
+	let sim = match &sim.0 {
+		Some(sim) if sim.is_running() && !locked.0 => sim,
+		_ => return,
+	};
+
+	let (window, cursor) = match (windows.get_primary(), pointer.cursor) {
+		(Some(window), Some(cursor)) => (window, cursor),
+		_ => return,
+	};
+
+	let mut target = None;
+	for (_tag, camera) in &mut cameras.iter() {
+		target = pick_cell(sim.map(), camera, window, cursor);
+	}
+
+	let directions = match target.and_then(|target| sim.path_to(target)) {
+		Some(directions) => directions,
+		None => return,
+	};
+
+	let mut pos = sim.active().current;
+	for dir in directions.iter() {
+		pos = pos.neighbour(*dir);
+		pointer.path.push(pos);
+	}
+
+	if buttons.just_pressed(MouseButton::Left) && !directions.is_empty() {
+		queue.set_path(&directions);
+	}
+}
+
+fn pointer_highlight(
+	mut commands: Commands,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut pointer: ResMut<Pointer>,
+	mut highlights: Query<(Entity, &PathHighlight)>,
+) {
+	if pointer.shown == pointer.path {
+		return;
+	}
+
+	for (ent, _tag) in &mut highlights.iter() {
+		commands.despawn(ent);
+	}
+
+	for pos in pointer.path.iter() {
+		commands.spawn(PbrComponents {
+				mesh: EntShape::BoostSquare.existing_mesh(&mut meshes),
+				material: pointer.material,
+				draw: Draw {
+					is_transparent: true,
+					..Default::default()
+				},
+				..Default::default()
+			})
+			.with(PathHighlight)
+			.with(DisplayGridPosition(*pos));
+	}
+
+	pointer.shown = pointer.path.clone();
+}
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+use ron::{
+	de::from_reader,
+	ser::{to_string_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::map::save;
+
+use super::audio::{SoundClass, StepEvent};
+use super::character::{announce_turn, CharacterCommand};
+use super::controls::InputMap;
+use super::motion::BumpEvent;
+use super::playback::TurnPlayback;
+use super::simulation::{SimStatus, Simulation};
+use super::{ActiveSimulation, ControlsLocked};
+
+const REPLAY_DIR_NAME: &str = "replays";
+
+pub const DEFAULT_REPLAY_SPEED: f32 = 4.0;
+
+/// A full run of one level: every loop's commands, oldest ghost first.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Replay {
+	/// Path of the level, as listed in the manifest.
+	pub level: String,
+	pub loops: Vec<Vec<CharacterCommand>>,
+}
+
+impl Replay {
+	pub fn from_simulation(level: &str, sim: &Simulation) -> Self {
+		Self {
+			level: level.to_string(),
+			loops: sim.characters().iter()
+				.map(|character| character.command_list.clone())
+				.collect(),
+		}
+	}
+
+	pub fn read(path: &str) -> Result<Self, String> {
+		let f = File::open(path)
+			.map_err(|e| format!("{}: could not be read: {}", path, e))?;
+
+		from_reader(f)
+			.map_err(|e| format!("{}: could not be parsed: {}", path, e))
+	}
+
+	pub fn write(&self, path: &Path) -> io::Result<()> {
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+
+		fs::write(path, to_string_pretty(self, PrettyConfig::new()).expect("Must ser"))
+	}
+
+	/// Picks a fresh file in the game's data directory, named after the level.
+	pub fn new_location(&self) -> Option<PathBuf> {
+		let name = Path::new(&self.level)
+			.file_name()
+			.and_then(|name| name.to_str())
+			.and_then(|name| name.split('.').next())
+			.unwrap_or("level");
+
+		let stamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or_default();
+
+		save::data_dir()
+			.map(|dir| dir.join(REPLAY_DIR_NAME).join(format!("{}-{}.ron", name, stamp)))
+	}
+
+	/// Every command in the order the player gave them.
+	pub fn commands(&self) -> Vec<CharacterCommand> {
+		self.loops.iter()
+			.flatten()
+			.copied()
+			.collect()
+	}
+
+	/// Feeds the whole replay into a simulation, stopping early if the level ends.
+	pub fn play(&self, sim: &mut Simulation) -> SimStatus {
+		for command in self.commands() {
+			if !sim.is_running() {
+				break;
+			}
+			sim.step(command);
+		}
+
+		sim.status()
+	}
+}
+
+/// Steps through a replay in real time.
+pub struct ReplayPlayer {
+	commands: Vec<CharacterCommand>,
+	next: usize,
+	timer: Timer,
+}
+
+impl ReplayPlayer {
+	pub fn new(replay: &Replay, turns_per_second: f32) -> Self {
+		Self {
+			commands: replay.commands(),
+			next: 0,
+			timer: Timer::from_seconds(1.0 / turns_per_second.max(0.01), true),
+		}
+	}
+}
+
+/// The replay currently driving the characters, if any.
+#[derive(Default)]
+pub struct ActiveReplay(pub Option<ReplayPlayer>);
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_resource(ActiveReplay::default())
+			.add_system(replay_driver.system());
+	}
+}
+
+fn replay_driver(
+	time: Res<Time>,
+	mut replay: ResMut<ActiveReplay>,
+	mut sim: ResMut<ActiveSimulation>,
+	mut locked: ResMut<ControlsLocked>,
+	map: Res<InputMap>,
+	mut playback: ResMut<TurnPlayback>,
+	mut evts: ResMut<Events<StepEvent>>,
+	mut sound_evts: ResMut<Events<SoundClass>>,
+	mut bump_evts: ResMut<Events<BumpEvent>>,
+) {
+	let (player, sim) = match (&mut replay.0, &mut sim.0) {
+		(Some(player), Some(sim)) => (player, sim),
+		_ => return,
+	};
+
+	locked.0 = true;
+
+	// Let the ghosts finish the last turn before starting the next.
+	if !playback.is_idle() {
+		return;
+	}
+
+	player.timer.tick(time.delta_seconds);
+	if !player.timer.just_finished {
+		return;
+	}
+
+	let next = player.commands.get(player.next)
+		.copied()
+		.filter(|_| sim.is_running());
+
+	match next {
+		Some(command) => {
+			let report = sim.step(command);
+			announce_turn(&report, &mut playback, map.ghost_delay, &mut evts, &mut sound_evts, &mut bump_evts);
+			player.next += 1;
+		},
+		// Held until the level is left, so the win isn't taken for the player's own.
+		None if sim.status() == SimStatus::Won => {},
+		None => {
+			// Hand control back: after a win, this is once the next level has begun.
+			replay.0 = None;
+			locked.0 = false;
+		},
+	}
+}
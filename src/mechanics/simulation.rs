@@ -0,0 +1,1086 @@
+use crate::map::{ChannelLogic, DoorClosing, EntData, Map};
+use std::collections::{BTreeMap, VecDeque};
+
+use super::buttons::SignalCounter;
+use super::character::{Character, CharacterCommand};
+use super::{
+	ActiveTurn,
+	Direction,
+	GridPosition,
+	OccupationMap,
+	DIRECTIONS,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SimStatus {
+	/// Turns can still be taken.
+	Running,
+	/// A character (active or ghost) has reached an `End` tile.
+	Won,
+	/// The final loop ran out of turns.
+	Stuck,
+	/// The active character was killed, by a hazard or a door; undo or rewind to carry on.
+	Died,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MoveOutcome {
+	Moved(GridPosition),
+	Blocked,
+	Waited,
+}
+
+/// One character's action within a turn.
+#[derive(Clone, Copy, Debug)]
+pub struct SubTurn {
+	/// Index into `WorldState::characters` (equal to that character's `my_turn`).
+	pub character: usize,
+	pub command: CharacterCommand,
+	pub from: GridPosition,
+	pub outcome: MoveOutcome,
+}
+
+/// Everything which happened as a result of one command from the player.
+#[derive(Clone, Debug, Default)]
+pub struct TurnReport {
+	/// Subturns in the order they were taken: active character first, then ghosts.
+	pub subturns: Vec<SubTurn>,
+	/// The turn limit was hit, and a new loop has begun.
+	pub looped: bool,
+	/// What any doors did to things in their way, in order.
+	pub doors: Vec<DoorEvent>,
+	/// Characters killed this turn, whether by hazards or doors.
+	pub deaths: Vec<usize>,
+}
+
+/// Something which happened when a door tried to close on whatever was standing in it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DoorEvent {
+	/// The door has to stay open until the way is clear.
+	Held(GridPosition),
+	/// The door shoved a character or crate out of the way.
+	Pushed {
+		from: GridPosition,
+		to: GridPosition,
+	},
+	/// The door closed on a character.
+	Crushed {
+		character: usize,
+		pos: GridPosition,
+	},
+}
+
+#[derive(Clone, Debug)]
+pub struct Door {
+	pub pos: GridPosition,
+	pub signal: usize,
+	pub open: bool,
+	/// Open only because something is in the way.
+	pub held: bool,
+	pub closing: DoorClosing,
+}
+
+/// A button which stays down for a while after it's left.
+#[derive(Clone, Debug)]
+struct Plate {
+	pos: GridPosition,
+	signal: usize,
+	turns: usize,
+}
+
+impl Plate {
+	fn is_down(&self, last_held: Option<usize>, turn: usize) -> bool {
+		last_held
+			.map(|held| turn <= held + self.turns)
+			.unwrap_or(false)
+	}
+}
+
+/// All state which changes as turns are taken.
+#[derive(Clone, Debug)]
+pub struct WorldState {
+	/// Indexed by `my_turn`: ghosts first (oldest at 0), active character last.
+	pub characters: Vec<Character>,
+	pub turn: ActiveTurn,
+	pub ghosts_left: usize,
+	pub signals: SignalCounter,
+	pub doors: Vec<Door>,
+	pub status: SimStatus,
+	/// `(character, turn)` for every move of the current loop which was blocked.
+	pub blocked: Vec<(usize, usize)>,
+	/// Where each crate is now, in the order they're listed in the map.
+	pub crates: Vec<GridPosition>,
+	/// Whether each lever is flipped on, in the order they're listed in the map.
+	pub levers: Vec<bool>,
+	/// The last turn of this loop on which each timed plate was stood on.
+	pub plates: Vec<Option<usize>>,
+}
+
+impl WorldState {
+	pub fn was_blocked(&self, character: usize, turn: usize) -> bool {
+		self.blocked.contains(&(character, turn))
+	}
+}
+
+/// The rules of a level, independent of any rendering or input.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+	map: Map,
+	buttons: Vec<(GridPosition, usize)>,
+	levers: Vec<(GridPosition, usize)>,
+	plates: Vec<Plate>,
+	logic: Vec<ChannelLogic>,
+	hazards: Vec<GridPosition>,
+	ends: Vec<GridPosition>,
+	/// Where each crate goes back to at the start of every loop.
+	crate_starts: Vec<GridPosition>,
+	state: WorldState,
+	/// States from before each turn of the current loop, most recent last.
+	history: Vec<WorldState>,
+}
+
+impl Simulation {
+	/// Builds a simulation from a level, placing the first character on its `Start`.
+	///
+	/// Returns `None` if the map has no `Start` entity.
+	pub fn new(map: Map) -> Option<Self> {
+		let mut start = None;
+		let mut buttons = vec![];
+		let mut levers = vec![];
+		let mut plates = vec![];
+		let mut ends = vec![];
+		let mut doors = vec![];
+		let mut crates = vec![];
+		let mut hazards = vec![];
+
+		for blueprint in map.ents.iter().flatten() {
+			let pos = blueprint.pos;
+			match &blueprint.data {
+				EntData::Start => {
+					start = start.or(Some(pos));
+				},
+				EntData::End => {
+					ends.push(pos);
+				},
+				EntData::Button(channel) => {
+					buttons.push((pos, channel.0));
+				},
+				EntData::Door(channel) => {
+					doors.push(Door {
+						pos,
+						signal: channel.0,
+						open: false,
+						held: false,
+						closing: DoorClosing::Wait,
+					});
+				},
+				EntData::HeavyDoor { channel, closing } => {
+					doors.push(Door {
+						pos,
+						signal: channel.0,
+						open: false,
+						held: false,
+						closing: *closing,
+					});
+				},
+				EntData::Crate => {
+					crates.push(pos);
+				},
+				EntData::Lever(channel) => {
+					levers.push((pos, channel.0));
+				},
+				EntData::Hazard(_) => {
+					hazards.push(pos);
+				},
+				EntData::TimedPlate { channel, turns } => {
+					plates.push(Plate {
+						pos,
+						signal: channel.0,
+						turns: *turns,
+					});
+				},
+			}
+		}
+
+		let ghosts_left = map.ghost_limit.unwrap_or_default().0;
+
+		let logic = map.channels.clone().unwrap_or_default();
+		let lever_count = levers.len();
+		let plate_count = plates.len();
+
+		start.map(|start| {
+			let mut out = Self {
+				map,
+				buttons,
+				levers,
+				plates,
+				logic,
+				hazards,
+				ends,
+				crate_starts: crates.clone(),
+				state: WorldState {
+					characters: vec![Character::new(start)],
+					turn: ActiveTurn::default(),
+					ghosts_left,
+					signals: SignalCounter::default(),
+					doors,
+					status: SimStatus::Running,
+					blocked: vec![],
+					crates,
+					levers: vec![false; lever_count],
+					plates: vec![None; plate_count],
+				},
+				history: vec![],
+			};
+
+			out.update_signals();
+
+			out
+		})
+	}
+
+	pub fn map(&self) -> &Map {
+		&self.map
+	}
+
+	pub fn state(&self) -> &WorldState {
+		&self.state
+	}
+
+	/// Returns to a state previously taken from `state()`.
+	pub fn restore(&mut self, state: WorldState) {
+		self.state = state;
+		self.history.clear();
+	}
+
+	/// Takes back the last turn of the current loop, if there is one.
+	///
+	/// Returns whether anything was undone.
+	pub fn undo(&mut self) -> bool {
+		if self.state.status == SimStatus::Won {
+			return false;
+		}
+
+		match self.history.pop() {
+			Some(state) => {
+				self.state = state;
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Starts the current loop over, keeping the ghosts recorded so far.
+	///
+	/// Returns whether there was anything to rewind.
+	pub fn rewind_loop(&mut self) -> bool {
+		if self.state.status == SimStatus::Won || self.active().command_list.is_empty() {
+			return false;
+		}
+
+		for character in self.state.characters.iter_mut() {
+			character.reset();
+		}
+
+		let active = self.active_index();
+		self.state.characters[active].command_list.clear();
+		self.state.turn.restart_loop();
+		self.state.status = SimStatus::Running;
+		self.state.blocked.clear();
+		self.state.crates = self.crate_starts.clone();
+		self.reset_switches();
+		self.history.clear();
+
+		self.update_signals();
+
+		true
+	}
+
+	pub fn status(&self) -> SimStatus {
+		self.state.status
+	}
+
+	pub fn is_running(&self) -> bool {
+		self.state.status == SimStatus::Running
+	}
+
+	pub fn turn_limit(&self) -> usize {
+		self.map.turn_limit.0
+	}
+
+	pub fn characters(&self) -> &[Character] {
+		&self.state.characters
+	}
+
+	/// Turns taken so far, counting every loop.
+	pub fn turns_taken(&self) -> usize {
+		self.state.characters.iter()
+			.map(|character| character.command_list.len())
+			.sum()
+	}
+
+	pub fn active_index(&self) -> usize {
+		self.state.characters.len() - 1
+	}
+
+	pub fn active(&self) -> &Character {
+		&self.state.characters[self.active_index()]
+	}
+
+	pub fn door_open(&self, pos: GridPosition) -> bool {
+		self.state.doors.iter()
+			.any(|door| door.pos == pos && door.open)
+	}
+
+	/// Whether the door at `pos` should have closed, but something is standing in it.
+	pub fn door_held(&self, pos: GridPosition) -> bool {
+		self.state.doors.iter()
+			.any(|door| door.pos == pos && door.held)
+	}
+
+	/// Whether the lever or timed plate at `pos` is currently sending its signal.
+	pub fn switch_down(&self, pos: GridPosition) -> bool {
+		let lever_on = self.levers.iter()
+			.zip(self.state.levers.iter())
+			.any(|(&(lever, _), &on)| lever == pos && on);
+
+		let plate_down = self.plates.iter()
+			.zip(self.state.plates.iter())
+			.any(|(plate, &held)| plate.pos == pos && plate.is_down(held, self.state.turn.turn));
+
+		lever_on || plate_down
+	}
+
+	/// The fewest moves which would take the active character to `target`, as things stand.
+	///
+	/// Walks around other characters, closed doors and hazards, but doesn't predict where ghosts will go.
+	pub fn path_to(&self, target: GridPosition) -> Option<Vec<Direction>> {
+		let start = self.active().current;
+		let occupation = self.occupation_except(Some(self.active_index()));
+
+		let mut paths = BTreeMap::new();
+		let mut queue = VecDeque::new();
+
+		paths.insert(start, vec![]);
+		queue.push_back(start);
+
+		while let Some(pos) = queue.pop_front() {
+			if pos == target {
+				break;
+			}
+
+			let path: Vec<Direction> = paths[&pos].clone();
+
+			for &direction in DIRECTIONS.iter() {
+				let next = pos.neighbour(direction);
+				if self.map.move_allowed_by_terrain(&pos, &next)
+					&& !occupation.0[self.index(next)]
+					&& !self.hazards.contains(&next)
+					&& !paths.contains_key(&next)
+				{
+					let mut next_path = path.clone();
+					next_path.push(direction);
+					paths.insert(next, next_path);
+					queue.push_back(next);
+				}
+			}
+		}
+
+		paths.remove(&target)
+	}
+
+	/// Cells blocked by characters, crates or closed doors.
+	pub fn occupation(&self) -> OccupationMap {
+		self.occupation_except(None)
+	}
+
+	/// Takes one full turn: the active character performs `command`, and then
+	/// each ghost replays its own next command (newest ghost first).
+	pub fn step(&mut self, command: CharacterCommand) -> TurnReport {
+		let were_dead: Vec<bool> = self.state.characters.iter()
+			.map(|character| character.dead)
+			.collect();
+
+		let mut report = self.take_turn(command);
+
+		report.deaths = self.state.characters.iter()
+			.zip(were_dead)
+			.enumerate()
+			.filter(|(_, (character, was_dead))| character.dead && !was_dead)
+			.map(|(i, _)| i)
+			.collect();
+
+		report
+	}
+
+	fn take_turn(&mut self, command: CharacterCommand) -> TurnReport {
+		let mut report = TurnReport::default();
+
+		if !self.is_running() {
+			return report;
+		}
+
+		self.history.push(self.state.clone());
+
+		let active = self.active_index();
+
+		loop {
+			let idx = self.state.turn.active_ent;
+			let character = &mut self.state.characters[idx];
+
+			// The dead sit out the rest of the loop, but ghosts keep their place in it.
+			if character.dead {
+				character.cmd_list_pos += 1;
+				self.state.turn.march_turn();
+
+				if idx == 0 {
+					break;
+				}
+				continue;
+			}
+
+			let action = if idx == active {
+				// ALWAYS push action regardless of whether or not it is doable.
+				character.command_list.push(command);
+				command
+			} else {
+				let action = character.command_list.get(character.cmd_list_pos)
+					.copied()
+					.unwrap_or(CharacterCommand::Wait);
+				character.cmd_list_pos += 1;
+				action
+			};
+
+			let subturn = self.act(idx, action);
+			match subturn.outcome {
+				MoveOutcome::Blocked => self.state.blocked.push((idx, self.state.turn.turn)),
+				MoveOutcome::Moved(pos) => {
+					self.flip_levers(pos);
+					if self.hazards.contains(&pos) {
+						self.kill(idx);
+					}
+				},
+				MoveOutcome::Waited => {},
+			}
+			report.subturns.push(subturn);
+			report.doors.extend(self.update_signals());
+
+			if !self.is_running() {
+				return report;
+			}
+
+			if self.reached_end() {
+				self.state.status = SimStatus::Won;
+				return report;
+			}
+
+			self.state.turn.march_turn();
+
+			if idx == 0 {
+				break;
+			}
+		}
+
+		// Timed plates may have run out now that the turn is over.
+		report.doors.extend(self.update_signals());
+
+		if !self.is_running() {
+			return report;
+		}
+
+		if self.reached_end() {
+			self.state.status = SimStatus::Won;
+			return report;
+		}
+
+		if self.state.turn.turn == self.turn_limit() {
+			if self.state.ghosts_left != 0 {
+				self.start_new_loop();
+				report.looped = true;
+			} else {
+				self.state.status = SimStatus::Stuck;
+			}
+		}
+
+		report
+	}
+
+	fn act(&mut self, idx: usize, command: CharacterCommand) -> SubTurn {
+		if let CharacterCommand::Move(dir) = command {
+			self.push_crate(idx, dir);
+		}
+
+		let occupation = self.occupation_except(Some(idx));
+		let from = self.state.characters[idx].current;
+		let to = from.destination(command);
+
+		let outcome = match command {
+			CharacterCommand::Wait => MoveOutcome::Waited,
+			CharacterCommand::Move(_) => {
+				// Terrain rules out leaving the map before the occupation lookup.
+				if self.map.move_allowed_by_terrain(&from, &to) && !occupation.0[self.index(to)] {
+					self.state.characters[idx].current = to;
+					MoveOutcome::Moved(to)
+				} else {
+					MoveOutcome::Blocked
+				}
+			},
+		};
+
+		SubTurn {
+			character: idx,
+			command,
+			from,
+			outcome,
+		}
+	}
+
+	/// Whether any live character is on an `End`, whether it walked there or was pushed.
+	fn reached_end(&self) -> bool {
+		self.state.characters.iter()
+			.any(|character| !character.dead && self.ends.contains(&character.current))
+	}
+
+	/// Takes a character out of play for the rest of the loop, which ends the attempt if it's the player.
+	fn kill(&mut self, idx: usize) {
+		self.state.characters[idx].dead = true;
+		if idx == self.active_index() {
+			self.state.status = SimStatus::Died;
+		}
+	}
+
+	fn flip_levers(&mut self, pos: GridPosition) {
+		for (&(lever, _), on) in self.levers.iter().zip(self.state.levers.iter_mut()) {
+			if lever == pos {
+				*on = !*on;
+			}
+		}
+	}
+
+	/// Shoves a crate out of the way of a character's move, if there's one there and it has room to go.
+	fn push_crate(&mut self, idx: usize, dir: Direction) {
+		let from = self.state.characters[idx].current;
+		let dest = from.neighbour(dir);
+		let beyond = dest.neighbour(dir);
+
+		let pushed = match self.state.crates.iter().position(|pos| *pos == dest) {
+			Some(pushed) => pushed,
+			None => return,
+		};
+
+		if !self.map.move_allowed_by_terrain(&from, &dest) || !self.map.push_allowed_by_terrain(&dest, &beyond) {
+			return;
+		}
+
+		if self.occupation().0[self.index(beyond)] {
+			return;
+		}
+
+		self.state.crates[pushed] = beyond;
+	}
+
+	fn start_new_loop(&mut self) {
+		let next = self.active().new_me();
+
+		for character in self.state.characters.iter_mut() {
+			character.reset();
+		}
+
+		self.state.characters.push(next);
+		self.state.turn.reset_and_add_ent();
+		self.state.ghosts_left -= 1;
+		self.state.blocked.clear();
+		self.state.crates = self.crate_starts.clone();
+		self.reset_switches();
+		self.history.clear();
+
+		self.update_signals();
+	}
+
+	fn reset_switches(&mut self) {
+		self.state.levers = vec![false; self.levers.len()];
+		self.state.plates = vec![None; self.plates.len()];
+	}
+
+	fn index(&self, pos: GridPosition) -> usize {
+		pos.unroll(self.map.width) as usize
+	}
+
+	fn occupation_except(&self, skip: Option<usize>) -> OccupationMap {
+		let mut occupation = OccupationMap(vec![false; self.map.len()]);
+
+		for (i, character) in self.state.characters.iter().enumerate() {
+			if Some(i) != skip && !character.dead {
+				occupation.0[self.index(character.current)] = true;
+			}
+		}
+
+		for door in self.state.doors.iter().filter(|door| !door.open) {
+			occupation.0[self.index(door.pos)] = true;
+		}
+
+		for pos in self.state.crates.iter() {
+			occupation.0[self.index(*pos)] = true;
+		}
+
+		occupation
+	}
+
+	/// Recounts every signal, then opens or closes doors to match.
+	///
+	/// Returns what any doors did to things in their way.
+	fn update_signals(&mut self) -> Vec<DoorEvent> {
+		let occupation = self.occupation();
+		let width = self.map.width;
+		let signals = &mut self.state.signals;
+
+		signals.reinit();
+		for &(pos, signal) in self.buttons.iter() {
+			signals.register_signal_source(signal);
+			if occupation.0[pos.unroll(width) as usize] {
+				signals.increment_signal(signal);
+			}
+		}
+
+		for (&(_, signal), &on) in self.levers.iter().zip(self.state.levers.iter()) {
+			signals.register_signal_source(signal);
+			if on {
+				signals.increment_signal(signal);
+			}
+		}
+
+		let turn = self.state.turn.turn;
+		for (plate, held) in self.plates.iter().zip(self.state.plates.iter_mut()) {
+			signals.register_signal_source(plate.signal);
+			if occupation.0[plate.pos.unroll(width) as usize] {
+				*held = Some(turn);
+			}
+			if plate.is_down(*held, turn) {
+				signals.increment_signal(plate.signal);
+			}
+		}
+
+		signals.apply_logic(&self.logic);
+
+		let mut events = vec![];
+		for i in 0..self.state.doors.len() {
+			let met = self.state.signals.signal_met(self.state.doors[i].signal);
+			events.extend(self.set_door(i, met));
+		}
+
+		events
+	}
+
+	fn set_door(&mut self, i: usize, open: bool) -> Option<DoorEvent> {
+		let door = self.state.doors[i].clone();
+
+		let character = self.state.characters.iter()
+			.position(|character| !character.dead && character.current == door.pos);
+		let pushed_crate = self.state.crates.iter()
+			.position(|pos| *pos == door.pos);
+
+		if open || !door.open || (character.is_none() && pushed_crate.is_none()) {
+			self.state.doors[i].open = open;
+			self.state.doors[i].held = false;
+			return None;
+		}
+
+		let event = match door.closing {
+			DoorClosing::Wait => None,
+			DoorClosing::Push => self.free_neighbour(door.pos, pushed_crate.is_some())
+				.map(|to| {
+					match (character, pushed_crate) {
+						(Some(character), _) => {
+							self.state.characters[character].current = to;
+							self.flip_levers(to);
+							if self.hazards.contains(&to) {
+								self.kill(character);
+							}
+						},
+						(None, Some(pushed)) => self.state.crates[pushed] = to,
+						(None, None) => {},
+					}
+
+					DoorEvent::Pushed {
+						from: door.pos,
+						to,
+					}
+				}),
+			DoorClosing::Crush => character.map(|character| {
+				self.kill(character);
+
+				DoorEvent::Crushed {
+					character,
+					pos: door.pos,
+				}
+			}),
+		};
+
+		if event.is_some() {
+			self.state.doors[i].open = false;
+			self.state.doors[i].held = false;
+			return event;
+		}
+
+		self.state.doors[i].held = true;
+		if door.held {
+			None
+		} else {
+			Some(DoorEvent::Held(door.pos))
+		}
+	}
+
+	/// The first cell next to `pos` which something there could be shoved into.
+	fn free_neighbour(&self, pos: GridPosition, is_crate: bool) -> Option<GridPosition> {
+		let occupation = self.occupation();
+
+		DIRECTIONS.iter()
+			.map(|dir| pos.neighbour(*dir))
+			.find(|to| {
+				let allowed = if is_crate {
+					self.map.push_allowed_by_terrain(&pos, to)
+				} else {
+					self.map.move_allowed_by_terrain(&pos, to)
+				};
+
+				allowed && !occupation.0[self.index(*to)]
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::{ActionChannel, Gate};
+	use crate::map::ascii::{AsciiMap, TileKind};
+	use crate::mechanics::{GhostLimit, TurnLimit};
+	use CharacterCommand::*;
+	use Direction::*;
+
+	/// Builds a simulation from grid rows, where `a`, `b` and `c` are open ground
+	/// at heights 0 to 2 and `x` is a wall.
+	fn sim(grid: &[&str], turn_limit: usize, ghost_limit: usize) -> Simulation {
+		sim_with_logic(grid, vec![], turn_limit, ghost_limit)
+	}
+
+	fn sim_with_logic(grid: &[&str], channels: Vec<ChannelLogic>, turn_limit: usize, ghost_limit: usize) -> Simulation {
+		let ascii = AsciiMap {
+			legend: vec![
+				('a', TileKind { height: 0, ..Default::default() }),
+				('b', TileKind { height: 1, ..Default::default() }),
+				('c', TileKind { height: 2, ..Default::default() }),
+				('x', TileKind { height: -1, ..Default::default() }),
+			].into_iter().collect(),
+			grid: grid.iter().map(|row| row.to_string()).collect(),
+			walls: None,
+			channels: Some(channels).filter(|channels| !channels.is_empty()),
+			turn_limit: TurnLimit(turn_limit),
+			ghost_limit: Some(GhostLimit(ghost_limit)),
+		};
+
+		Simulation::new(ascii.into_map().unwrap()).unwrap()
+	}
+
+	fn pos(x: isize, y: isize) -> GridPosition {
+		GridPosition { x, y }
+	}
+
+	fn rule(channel: usize, gate: Gate, inputs: &[usize]) -> ChannelLogic {
+		ChannelLogic {
+			channel: ActionChannel(channel),
+			gate,
+			inputs: inputs.iter().copied().map(ActionChannel).collect(),
+		}
+	}
+
+	#[test]
+	fn moves_and_blocks() {
+		let mut sim = sim(&["aS a x aE"], 8, 0);
+
+		let report = sim.step(Move(North));
+		assert_eq!(report.subturns[0].outcome, MoveOutcome::Moved(pos(1, 0)));
+
+		let report = sim.step(Move(North));
+		assert_eq!(report.subturns[0].outcome, MoveOutcome::Blocked);
+		assert!(sim.state().was_blocked(0, 1));
+
+		let report = sim.step(Move(East));
+		assert_eq!(report.subturns[0].outcome, MoveOutcome::Blocked);
+		assert_eq!(sim.active().current, pos(1, 0));
+	}
+
+	#[test]
+	fn ghosts_act_after_the_active_character_newest_first() {
+		let mut sim = sim(&["aS a a aE"], 1, 2);
+
+		assert!(sim.step(Wait).looped);
+		assert!(sim.step(Wait).looped);
+
+		let report = sim.step(Wait);
+		let order: Vec<usize> = report.subturns.iter()
+			.map(|subturn| subturn.character)
+			.collect();
+		assert_eq!(order, vec![2, 1, 0]);
+	}
+
+	#[test]
+	fn button_opens_door() {
+		let mut sim = sim(&["aS aB0 a aE", "x  aD0 x x"], 8, 0);
+		let door = pos(1, 1);
+
+		assert!(!sim.door_open(door));
+		assert!(sim.occupation().0[sim.index(door)]);
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+		assert!(!sim.occupation().0[sim.index(door)]);
+
+		sim.step(Move(North));
+		assert!(!sim.door_open(door));
+	}
+
+	#[test]
+	fn loop_rolls_over_into_a_ghost() {
+		let mut sim = sim(&["aS a a aE"], 2, 1);
+
+		assert!(!sim.step(Move(North)).looped);
+		assert!(sim.step(Move(North)).looped);
+
+		assert_eq!(sim.characters().len(), 2);
+		assert_eq!(sim.state().ghosts_left, 0);
+		assert_eq!(sim.state().turn.turn, 0);
+		assert_eq!(sim.characters()[0].current, pos(0, 0));
+		assert_eq!(sim.characters()[0].command_list, vec![Move(North), Move(North)]);
+		assert_eq!(sim.active().current, pos(0, 0));
+
+		let report = sim.step(Wait);
+		assert_eq!(report.subturns[1].outcome, MoveOutcome::Moved(pos(1, 0)));
+		assert_eq!(sim.characters()[0].current, pos(1, 0));
+	}
+
+	#[test]
+	fn reaching_the_end_wins() {
+		let mut sim = sim(&["aS a aE"], 8, 0);
+
+		sim.step(Move(North));
+		assert_eq!(sim.status(), SimStatus::Running);
+
+		sim.step(Move(North));
+		assert_eq!(sim.status(), SimStatus::Won);
+		assert!(!sim.undo());
+		assert!(sim.step(Wait).subturns.is_empty());
+	}
+
+	/// Records a ghost which flips the lever and stands in the doorway, then flips it back.
+	fn push_ghost_out_of_door(sim: &mut Simulation) {
+		sim.step(Move(North));
+		sim.step(Move(East));
+		assert!(sim.step(Wait).looped);
+
+		sim.step(Wait);
+		sim.step(Wait);
+		assert!(sim.door_open(pos(1, 1)));
+
+		let report = sim.step(Move(North));
+		assert_eq!(report.doors, vec![DoorEvent::Pushed { from: pos(1, 1), to: pos(2, 1) }]);
+	}
+
+	#[test]
+	fn pushed_onto_the_end_wins() {
+		let mut sim = sim(&["aS aL0 a", "x aD0p aE"], 3, 1);
+
+		push_ghost_out_of_door(&mut sim);
+		assert_eq!(sim.status(), SimStatus::Won);
+	}
+
+	#[test]
+	fn pushed_onto_a_lever_flips_it() {
+		let mut sim = sim(&["aS aL0 a", "x aD0p aL1", "x x aE"], 3, 1);
+
+		push_ghost_out_of_door(&mut sim);
+		assert!(sim.switch_down(pos(2, 1)));
+	}
+
+	#[test]
+	fn paths_avoid_hazards() {
+		let mut sim = sim(&["aS a^ a", "a  a  a"], 8, 0);
+
+		assert_eq!(sim.path_to(pos(2, 0)), Some(vec![East, North, North, West]));
+		assert_eq!(sim.path_to(pos(1, 0)), None);
+
+		sim.step(Move(North));
+		sim.step(Move(North));
+		assert_eq!(sim.status(), SimStatus::Died);
+	}
+
+	#[test]
+	fn running_out_of_turns_and_ghosts_is_stuck() {
+		let mut sim = sim(&["aS a aE"], 1, 0);
+
+		let report = sim.step(Wait);
+		assert!(!report.looped);
+		assert_eq!(sim.status(), SimStatus::Stuck);
+
+		assert!(sim.undo());
+		assert_eq!(sim.status(), SimStatus::Running);
+	}
+
+	#[test]
+	fn at_least_gate_counts_sources() {
+		let mut sim = sim_with_logic(&["aS aL0 aL0 aL0 aE", "aD0 x x x x"], vec![rule(0, Gate::AtLeast(2), &[])], 8, 0);
+		let door = pos(0, 1);
+
+		sim.step(Move(North));
+		assert!(!sim.door_open(door));
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+	}
+
+	#[test]
+	fn all_gate_needs_every_source() {
+		let mut sim = sim_with_logic(&["aS aL0 aL0 aE", "aD0 x x x"], vec![rule(0, Gate::All, &[])], 8, 0);
+		let door = pos(0, 1);
+
+		sim.step(Move(North));
+		assert!(!sim.door_open(door));
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+	}
+
+	#[test]
+	fn not_gate_closes_when_pressed() {
+		let mut sim = sim_with_logic(&["aS aB0 a aE", "aD0 x x x"], vec![rule(0, Gate::Not, &[])], 8, 0);
+		let door = pos(0, 1);
+
+		assert!(sim.door_open(door));
+
+		sim.step(Move(North));
+		assert!(!sim.door_open(door));
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+	}
+
+	#[test]
+	fn not_gate_without_sources_holds_its_door_open() {
+		let mut sim = sim_with_logic(&["aS a aE", "aD0 x x"], vec![rule(0, Gate::Not, &[])], 8, 0);
+
+		assert!(sim.door_open(pos(0, 1)));
+		sim.step(Move(North));
+		assert!(sim.door_open(pos(0, 1)));
+	}
+
+	#[test]
+	fn channels_feed_the_channels_which_take_them_as_inputs() {
+		let logic = vec![
+			rule(1, Gate::All, &[0]),
+			rule(2, Gate::Not, &[1]),
+		];
+		let mut sim = sim_with_logic(&["aS aL0 a aE", "aD1 x aD2 x"], logic, 8, 0);
+
+		assert!(!sim.door_open(pos(0, 1)));
+		assert!(sim.door_open(pos(2, 1)));
+
+		sim.step(Move(North));
+		assert!(sim.door_open(pos(0, 1)));
+		assert!(!sim.door_open(pos(2, 1)));
+	}
+
+	#[test]
+	fn crates_climb_small_steps_and_drop_any_height() {
+		// (under the character, under the crate, beyond the crate, whether it's pushed)
+		let cases = [
+			("a", "a", "a", true),
+			("a", "a", "b", true),
+			("a", "a", "c", false),
+			("c", "c", "a", true),
+			("a", "c", "a", false),
+			("a", "a", "x", false),
+		];
+
+		for &(start, under, beyond, pushed) in cases.iter() {
+			let row = format!("{}S {}C {} a aE", start, under, beyond);
+			let mut sim = sim(&[&row], 8, 0);
+
+			sim.step(Move(North));
+			let expected = if pushed { pos(2, 0) } else { pos(1, 0) };
+			assert_eq!(sim.state().crates, vec![expected], "{}", row);
+		}
+	}
+
+	#[test]
+	fn crates_hold_buttons_down() {
+		let mut sim = sim(&["aS aC aB0 a aE", "x x x aD0 x"], 8, 0);
+		let door = pos(3, 1);
+
+		sim.step(Move(North));
+		assert_eq!(sim.state().crates, vec![pos(2, 0)]);
+		assert!(sim.door_open(door));
+
+		sim.step(Wait);
+		assert!(sim.door_open(door));
+	}
+
+	#[test]
+	fn crates_are_not_pushed_into_closed_doors() {
+		let mut sim = sim(&["aS aC aD0 aE", "aB0 x x x"], 8, 0);
+
+		let report = sim.step(Move(North));
+		assert_eq!(report.subturns[0].outcome, MoveOutcome::Blocked);
+		assert_eq!(sim.state().crates, vec![pos(1, 0)]);
+	}
+
+	#[test]
+	fn crates_go_back_to_their_start_each_loop() {
+		let mut sim = sim(&["aS aC a a aE"], 1, 1);
+
+		assert!(sim.step(Move(North)).looped);
+		assert_eq!(sim.state().crates, vec![pos(1, 0)]);
+
+		sim.step(Wait);
+		assert_eq!(sim.state().crates, vec![pos(2, 0)]);
+	}
+
+	#[test]
+	fn levers_flip_once_per_step_onto_them() {
+		let mut sim = sim(&["aS aL0 a aE", "x aD0 x x"], 8, 0);
+		let lever = pos(1, 0);
+
+		// (command, whether the lever is on afterwards)
+		let steps = [
+			(Move(North), true),
+			(Wait, true),
+			(Move(North), true),
+			(Move(South), false),
+			(Move(South), false),
+			(Move(North), true),
+		];
+
+		for (i, &(command, on)) in steps.iter().enumerate() {
+			sim.step(command);
+			assert_eq!(sim.switch_down(lever), on, "step {}", i);
+			assert_eq!(sim.door_open(pos(1, 1)), on, "step {}", i);
+		}
+	}
+
+	#[test]
+	fn timed_plates_stay_down_for_their_turns() {
+		let mut sim = sim(&["aS aT0/2 a aE", "x x x aD0"], 8, 0);
+		let door = pos(3, 1);
+
+		sim.step(Move(North));
+		assert!(sim.door_open(door));
+		sim.step(Move(South));
+
+		// (whether the door is open after each turn off the plate)
+		for (i, &open) in [true, true, false].iter().enumerate() {
+			assert_eq!(sim.door_open(door), open, "turn {}", i);
+			sim.step(Wait);
+		}
+	}
+}
@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::map::Map;
+
+use super::character::CharacterCommand;
+use super::simulation::{SimStatus, Simulation, WorldState};
+use super::{Direction, GridPosition, DIRECTIONS};
+
+pub const DEFAULT_STEP_BUDGET: usize = 2_000_000;
+
+const COMMANDS: [CharacterCommand; 5] = [
+	CharacterCommand::Wait,
+	CharacterCommand::Move(Direction::North),
+	CharacterCommand::Move(Direction::East),
+	CharacterCommand::Move(Direction::South),
+	CharacterCommand::Move(Direction::West),
+];
+
+/// The commands given in each loop, oldest first.
+///
+/// Every loop but the last runs for the full turn limit; the last stops once the level is won.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Solution {
+	pub loops: Vec<Vec<CharacterCommand>>,
+}
+
+impl Solution {
+	fn from_state(state: &WorldState) -> Self {
+		Self {
+			loops: state.characters.iter()
+				.map(|character| character.command_list.clone())
+				.collect(),
+		}
+	}
+
+	pub fn ghosts_used(&self) -> usize {
+		self.loops.len().saturating_sub(1)
+	}
+
+	pub fn final_turns(&self) -> usize {
+		self.loops.last()
+			.map(Vec::len)
+			.unwrap_or_default()
+	}
+
+	fn better_than(&self, other: &Option<Solution>) -> bool {
+		other.as_ref()
+			.map(|other| (self.loops.len(), self.final_turns()) < (other.loops.len(), other.final_turns()))
+			.unwrap_or(true)
+	}
+}
+
+impl fmt::Display for Solution {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (i, commands) in self.loops.iter().enumerate() {
+			write!(f, "loop {}: ", i)?;
+			for command in commands {
+				write!(f, "{}", command_char(*command))?;
+			}
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+}
+
+fn command_char(command: CharacterCommand) -> char {
+	match command {
+		CharacterCommand::Wait => '.',
+		CharacterCommand::Move(Direction::North) => 'N',
+		CharacterCommand::Move(Direction::East) => 'E',
+		CharacterCommand::Move(Direction::South) => 'S',
+		CharacterCommand::Move(Direction::West) => 'W',
+	}
+}
+
+#[derive(Clone, Debug)]
+pub enum Verdict {
+	/// `minimal` is only set if every solution using fewer loops, or fewer
+	/// turns in the last loop, was ruled out.
+	Solved {
+		solution: Solution,
+		minimal: bool,
+	},
+	/// Every command sequence was tried, and none reach an `End`.
+	Unsolvable,
+	/// The step budget ran out before anything was found.
+	Undecided,
+}
+
+enum Search {
+	Found(Solution),
+	Exhausted,
+	OutOfBudget(Option<Solution>),
+}
+
+/// Searches for the shortest way through a level: fewest loops first, then
+/// fewest turns in the final loop.
+///
+/// Every loop but the last is searched exhaustively (depth-first over all
+/// commands), while the final loop is a breadth-first search over world states,
+/// since what the active character did no longer matters once no ghost will
+/// replay it. When that runs out of budget, earlier loops are restricted to
+/// "wait, walk to a cell, then hold" plans, which covers most puzzles cheaply
+/// but can't prove anything.
+pub struct Solver {
+	sim: Simulation,
+	budget: usize,
+	steps: usize,
+}
+
+impl Solver {
+	/// Returns `None` if the map has no `Start` entity.
+	pub fn new(map: Map) -> Option<Self> {
+		Simulation::new(map).map(|sim| Self {
+			sim,
+			budget: DEFAULT_STEP_BUDGET,
+			steps: 0,
+		})
+	}
+
+	/// Sets how many turns may be simulated by each phase of the search.
+	pub fn with_budget(mut self, budget: usize) -> Self {
+		self.budget = budget;
+		self
+	}
+
+	pub fn solve(mut self) -> Verdict {
+		let start = self.sim.state().clone();
+		let max_ghosts = start.ghosts_left;
+
+		for ghosts in 0..=max_ghosts {
+			self.sim.restore(start.clone());
+
+			let mut best = None;
+			let complete = self.record(ghosts, &mut best);
+
+			let search = if complete {
+				best.map(Search::Found).unwrap_or(Search::Exhausted)
+			} else {
+				Search::OutOfBudget(best)
+			};
+
+			match search {
+				Search::Found(solution) => {
+					return Verdict::Solved {
+						solution,
+						minimal: true,
+					};
+				},
+				Search::Exhausted => {},
+				Search::OutOfBudget(Some(solution)) => {
+					return Verdict::Solved {
+						solution,
+						minimal: false,
+					};
+				},
+				Search::OutOfBudget(None) => {
+					return self.planned(&start, ghosts..=max_ghosts)
+						.map(|solution| Verdict::Solved {
+							solution,
+							minimal: false,
+						})
+						.unwrap_or(Verdict::Undecided);
+				},
+			}
+		}
+
+		Verdict::Unsolvable
+	}
+
+	fn out_of_budget(&self) -> bool {
+		self.steps >= self.budget
+	}
+
+	fn step(&mut self, command: CharacterCommand) -> bool {
+		self.steps += 1;
+		self.sim.step(command).looped
+	}
+
+	/// Tries every command for the remainder of the current (non-final) loop.
+	///
+	/// Returns `false` if the budget ran out before the search was complete.
+	fn record(&mut self, ghosts: usize, best: &mut Option<Solution>) -> bool {
+		if self.sim.active_index() == ghosts {
+			if let Some(solution) = self.final_loop() {
+				if solution.better_than(best) {
+					*best = Some(solution);
+				}
+			}
+
+			return !self.out_of_budget();
+		}
+
+		let state = self.sim.state().clone();
+
+		for &command in COMMANDS.iter() {
+			if self.out_of_budget() {
+				return false;
+			}
+
+			self.step(command);
+
+			match self.sim.status() {
+				SimStatus::Won => {
+					let solution = Solution::from_state(self.sim.state());
+					if solution.better_than(best) {
+						*best = Some(solution);
+					}
+				},
+				SimStatus::Running => {
+					if !self.record(ghosts, best) {
+						return false;
+					}
+				},
+				SimStatus::Stuck | SimStatus::Died => {},
+			}
+
+			self.sim.restore(state.clone());
+		}
+
+		true
+	}
+
+	/// Finds the fewest turns needed to win from the current state without looping again.
+	fn final_loop(&mut self) -> Option<Solution> {
+		let start = self.sim.state().clone();
+		let mut seen = HashSet::new();
+		let mut queue = VecDeque::new();
+
+		seen.insert(state_key(&start));
+		queue.push_back(start);
+
+		while let Some(state) = queue.pop_front() {
+			for &command in COMMANDS.iter() {
+				if self.out_of_budget() {
+					return None;
+				}
+
+				self.sim.restore(state.clone());
+				let looped = self.step(command);
+
+				match self.sim.status() {
+					SimStatus::Won => {
+						return Some(Solution::from_state(self.sim.state()));
+					},
+					SimStatus::Running if !looped => {
+						let next = self.sim.state().clone();
+						if seen.insert(state_key(&next)) {
+							queue.push_back(next);
+						}
+					},
+					_ => {},
+				}
+			}
+		}
+
+		None
+	}
+
+	fn planned(&mut self, start: &WorldState, ghost_counts: std::ops::RangeInclusive<usize>) -> Option<Solution> {
+		self.steps = 0;
+
+		let from = start.characters[0].start;
+		let plans = plans(self.sim.map(), from, self.sim.turn_limit());
+
+		for ghosts in ghost_counts {
+			self.sim.restore(start.clone());
+
+			let mut best = None;
+			self.follow_plans(ghosts, &plans, &mut best);
+
+			if best.is_some() || self.out_of_budget() {
+				return best;
+			}
+		}
+
+		None
+	}
+
+	fn follow_plans(&mut self, ghosts: usize, plans: &[Vec<CharacterCommand>], best: &mut Option<Solution>) {
+		if self.sim.active_index() == ghosts {
+			if let Some(solution) = self.final_loop() {
+				if solution.better_than(best) {
+					*best = Some(solution);
+				}
+			}
+
+			return;
+		}
+
+		let state = self.sim.state().clone();
+
+		for plan in plans {
+			for &command in plan {
+				if self.out_of_budget() {
+					return;
+				}
+
+				let looped = self.step(command);
+
+				if looped || !self.sim.is_running() {
+					break;
+				}
+			}
+
+			match self.sim.status() {
+				SimStatus::Won => {
+					let solution = Solution::from_state(self.sim.state());
+					if solution.better_than(best) {
+						*best = Some(solution);
+					}
+				},
+				SimStatus::Running => {
+					self.follow_plans(ghosts, plans, best);
+				},
+				SimStatus::Stuck | SimStatus::Died => {},
+			}
+
+			self.sim.restore(state.clone());
+		}
+	}
+}
+
+/// Everything which can differ between two states of the final loop.
+fn state_key(state: &WorldState) -> (usize, Vec<GridPosition>, Vec<bool>, Vec<Option<usize>>) {
+	(
+		state.turn.turn,
+		state.characters.iter()
+			.map(|character| character.current)
+			.chain(state.crates.iter().copied())
+			.collect(),
+		state.levers.iter()
+			.copied()
+			.chain(state.characters.iter().map(|character| character.dead))
+			.collect(),
+		state.plates.clone(),
+	)
+}
+
+/// Every "wait, take the shortest path to a cell, then wait" loop which fits in `turn_limit`.
+///
+/// Plans which end on an entity (e.g., a button) are tried first.
+fn plans(map: &Map, start: GridPosition, turn_limit: usize) -> Vec<Vec<CharacterCommand>> {
+	let mut paths = BTreeMap::new();
+	let mut queue = VecDeque::new();
+
+	paths.insert(start, vec![]);
+	queue.push_back(start);
+
+	while let Some(pos) = queue.pop_front() {
+		let path: Vec<CharacterCommand> = paths[&pos].clone();
+		if path.len() >= turn_limit {
+			continue;
+		}
+
+		for &direction in DIRECTIONS.iter() {
+			let next = pos.neighbour(direction);
+			if map.move_allowed_by_terrain(&pos, &next) && !paths.contains_key(&next) {
+				let mut next_path = path.clone();
+				next_path.push(CharacterCommand::Move(direction));
+				paths.insert(next, next_path);
+				queue.push_back(next);
+			}
+		}
+	}
+
+	let interesting: HashSet<GridPosition> = map.ents.iter()
+		.flatten()
+		.map(|blueprint| blueprint.pos)
+		.collect();
+
+	let mut targets: Vec<_> = paths.into_iter().collect();
+	targets.sort_by_key(|(pos, _path)| !interesting.contains(pos));
+
+	let mut out = vec![];
+	for (_pos, path) in targets.iter() {
+		for delay in 0..=(turn_limit - path.len()) {
+			let mut plan = vec![CharacterCommand::Wait; delay];
+			plan.extend_from_slice(path);
+			plan.resize(turn_limit, CharacterCommand::Wait);
+			out.push(plan);
+		}
+	}
+
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::meta::{Levels, LEVEL_MANIFEST_LOCATION};
+
+	#[test]
+	fn every_level_in_the_manifest_is_solved() {
+		let levels = Levels::from_path(LEVEL_MANIFEST_LOCATION)
+			.unwrap_or_else(|e| panic!("{}", e));
+
+		for level in levels.data.iter() {
+			let map = level.get_map()
+				.unwrap_or_else(|e| panic!("{}", e));
+
+			let verdict = Solver::new(map).map(Solver::solve);
+			assert!(
+				matches!(verdict, Some(Verdict::Solved { .. })),
+				"{} ({}): {:?}",
+				level.name,
+				level.path,
+				verdict,
+			);
+		}
+	}
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+
+use super::audio::SoundClass;
+use super::{ActiveSimulation, GridPosition};
+
+/// A lever or timed plate, drawn up or down to match the simulation.
+pub struct SwitchBody {
+	pos: GridPosition,
+	up: Handle<StandardMaterial>,
+	down: Handle<StandardMaterial>,
+	was_down: bool,
+}
+
+impl SwitchBody {
+	pub fn new(pos: GridPosition, up: Handle<StandardMaterial>, down: Handle<StandardMaterial>) -> Self {
+		Self {
+			pos,
+			up,
+			down,
+			was_down: false,
+		}
+	}
+}
+
+pub struct SwitchPlugin;
+
+impl Plugin for SwitchPlugin {
+	fn build(&self, app: &mut AppBuilder) {
+		app.add_system(switch_sync.system());
+	}
+}
+
+fn switch_sync(
+	sim: Res<ActiveSimulation>,
+	mut evts: ResMut<Events<SoundClass>>,
+	mut query: Query<(&mut SwitchBody, &mut Handle<StandardMaterial>)>,
+) {
+	if let Some(sim) = &sim.0 {
+		for (mut body, mut material) in &mut query.iter() {
+			let down = sim.switch_down(body.pos);
+			if down != body.was_down {
+				*material = if down { body.down } else { body.up };
+				evts.send(SoundClass::Button);
+				body.was_down = down;
+			}
+		}
+	}
+}
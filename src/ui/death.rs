@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+use crate::mechanics::ActiveSimulation;
+use crate::mechanics::controls::{Action, InputMap};
+use crate::mechanics::simulation::SimStatus;
+
+use super::menu::Menu;
+
+const DEATH_COLOUR: Color = Color::rgb(0.9, 0.15, 0.1);
+
+/// One line of the overlay shown when the player dies.
+#[derive(Debug, Default)]
+pub struct DeathLine(usize);
+
+pub fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut fonts: ResMut<Assets<Font>>,
+) {
+	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
+
+	for i in 0..2 {
+		commands.spawn(TextComponents {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: Rect {
+						top: Val::Percent(35.0 + 10.0 * i as f32),
+						left: Val::Percent(25.0),
+						..Default::default()
+					},
+					..Default::default()
+				},
+				text: Text {
+					value: "".to_string(),
+					font: font_handle,
+					style: TextStyle {
+						font_size: if i == 0 { 80.0 } else { 30.0 },
+						color: if i == 0 { DEATH_COLOUR } else { Color::WHITE },
+					}
+				},
+				..Default::default()
+			})
+			.with(DeathLine(i));
+	}
+}
+
+pub fn death_display(
+	sim: Res<ActiveSimulation>,
+	menu: Res<Menu>,
+	map: Res<InputMap>,
+	mut query: Query<(&DeathLine, &mut Text)>,
+) {
+	let died = match &sim.0 {
+		Some(sim) => sim.status() == SimStatus::Died && !menu.open,
+		None => false,
+	};
+
+	for (line, mut text) in &mut query.iter() {
+		let value = match (died, line.0) {
+			(true, 0) => "You died.".to_string(),
+			(true, _) => format!("Move to try this loop again, or {} to undo your last turn.", map.key_name(Action::Undo)),
+			(false, _) => String::new(),
+		};
+
+		if text.value != value {
+			text.value = value;
+		}
+	}
+}
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use numerals::roman::Roman;
+
+use crate::map::meta::Levels;
+use crate::map::save::SaveData;
+use crate::mechanics::ControlsLocked;
+use crate::mechanics::controls::{Action, InputMap};
+use crate::mechanics::events::Restart;
+
+const MENU_LINES: usize = 12;
+const HEADER_LINES: usize = 2;
+
+const SELECTED_COLOUR: Color = Color::rgb(1.0, 0.8, 0.2);
+const UNBEATEN_COLOUR: Color = Color::rgb(0.7, 0.7, 0.7);
+
+/// The title screen and level select, shown at startup and on Escape.
+#[derive(Debug)]
+pub struct Menu {
+	pub open: bool,
+	selected: usize,
+}
+
+impl Default for Menu {
+	fn default() -> Self {
+		Self {
+			open: true,
+			selected: 0,
+		}
+	}
+}
+
+impl Menu {
+	pub fn closed() -> Self {
+		Self {
+			open: false,
+			..Default::default()
+		}
+	}
+}
+
+/// One line of the menu: the first `HEADER_LINES` are the title, the rest list levels.
+#[derive(Debug, Default)]
+pub struct MenuLine(usize);
+
+pub fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut fonts: ResMut<Assets<Font>>,
+	levels: Res<Levels>,
+	mut menu: ResMut<Menu>,
+) {
+	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
+
+	for i in 0..MENU_LINES {
+		commands.spawn(TextComponents {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: Rect {
+						top: Val::Percent(if i == 0 { 10.0 } else { 12.0 + 6.0 * i as f32 }),
+						left: Val::Percent(10.0),
+						..Default::default()
+					},
+					..Default::default()
+				},
+				text: Text {
+					value: "".to_string(),
+					font: font_handle,
+					style: TextStyle {
+						font_size: if i == 0 { 60.0 } else { 30.0 },
+						color: Color::WHITE,
+					}
+				},
+				..Default::default()
+			})
+			.with(MenuLine(i));
+	}
+
+	menu.selected = levels.start_at;
+}
+
+pub fn menu_control(
+	actions: Res<Input<Action>>,
+	mut menu: ResMut<Menu>,
+	mut locked: ResMut<ControlsLocked>,
+	mut levels: ResMut<Levels>,
+	mut restarts: ResMut<Events<Restart>>,
+) {
+	if !menu.open {
+		if actions.just_pressed(Action::Menu) && !locked.0 {
+			menu.open = true;
+			menu.selected = levels.start_at;
+			locked.0 = true;
+		}
+		return;
+	}
+
+	locked.0 = true;
+
+	let count = levels.data.len();
+	for action in actions.get_just_pressed() {
+		match action {
+			Action::MoveNorth => {
+				menu.selected = menu.selected.saturating_sub(1);
+			},
+			Action::MoveSouth => {
+				menu.selected = (menu.selected + 1).min(count.saturating_sub(1));
+			},
+			Action::Confirm | Action::Wait => {
+				if menu.selected < count {
+					levels.start_at = menu.selected;
+					restarts.send(Restart);
+				}
+				menu.open = false;
+
+				// Confirm and Wait share a gamepad button, which mustn't restart twice.
+				break;
+			},
+			Action::Menu => {
+				menu.open = false;
+			},
+			_ => {},
+		}
+	}
+
+	if !menu.open {
+		locked.0 = false;
+	}
+}
+
+pub fn menu_display(
+	menu: Res<Menu>,
+	levels: Res<Levels>,
+	save: Res<SaveData>,
+	map: Res<InputMap>,
+	mut query: Query<(&MenuLine, &mut Text)>,
+) {
+	let mut lines = vec![];
+
+	if menu.open {
+		lines.push(("Multitasking".to_string(), Color::WHITE));
+		let hint = format!(
+			"{}/{} to choose a level, {} to play, {} to go back.",
+			map.key_name(Action::MoveNorth),
+			map.key_name(Action::MoveSouth),
+			map.key_name(Action::Confirm),
+			map.key_name(Action::Menu),
+		);
+		lines.push((hint, UNBEATEN_COLOUR));
+
+		if levels.data.is_empty() {
+			lines.push(("No levels could be found.".to_string(), UNBEATEN_COLOUR));
+		}
+
+		// Scroll so that the selected level is always on screen.
+		let rows = MENU_LINES - HEADER_LINES;
+		let first = (menu.selected + 1).saturating_sub(rows);
+
+		for (i, level) in levels.data.iter().enumerate().skip(first).take(rows) {
+			let romanify: Roman = (i as i16 + 1).into();
+			let mut line = format!("{:X}: {}", romanify, level.name);
+
+			let colour = if let Some(record) = save.completed.get(&level.path) {
+				line.push_str(&format!(
+					" (best: {} turns, {} loops)",
+					record.best_turns,
+					record.best_loops,
+				));
+				Color::WHITE
+			} else {
+				UNBEATEN_COLOUR
+			};
+
+			if i == menu.selected {
+				lines.push((format!("> {}", line), SELECTED_COLOUR));
+			} else {
+				lines.push((line, colour));
+			}
+		}
+	}
+
+	for (line, mut text) in &mut query.iter() {
+		let (value, colour) = lines.get(line.0)
+			.cloned()
+			.unwrap_or((String::new(), Color::WHITE));
+
+		if text.value != value {
+			text.value = value;
+		}
+		text.style.color = colour;
+	}
+}
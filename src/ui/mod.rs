@@ -1,10 +1,17 @@
+pub mod death;
+pub mod menu;
+pub mod paths;
+pub mod timeline;
+
 use std::time::Duration;
 
 use bevy::{
 	diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
 	prelude::*,
 };
+use crate::map::meta::LevelFailure;
 use crate::mechanics::GhostLimit;
+use crate::mechanics::controls::{Action, InputMap};
 use crate::mechanics::events::SpawnLevelText;
 use crate::{
 	mechanics::{ActiveTurn, TurnLimit},
@@ -35,11 +42,11 @@ fn fps_update_system(
 }
 
 fn fps_control_system(
-	key_input: Res<Input<KeyCode>>,
+	actions: Res<Input<Action>>,
 	mut query: Query<&mut FpsCounter>,
 ) {
 
-	if key_input.just_pressed(KeyCode::F1) {
+	if actions.just_pressed(Action::ToggleFps) {
 		for mut counter in &mut query.iter() {
 			counter.enabled = !counter.enabled;
 		}
@@ -89,6 +96,45 @@ fn reruns_recolour_system(
 	}	
 }
 
+const ERROR_LINES: usize = 10;
+
+/// One line of the screen shown when a level can't be loaded.
+#[derive(Debug, Default)]
+pub struct ErrorLine(usize);
+
+fn level_failure_system(
+	failure: Res<LevelFailure>,
+	map: Res<InputMap>,
+	mut query: Query<(&ErrorLine, &mut Text)>,
+) {
+	let lines = match &failure.0 {
+		Some(e) => {
+			let mut details = e.details();
+			if details.len() > ERROR_LINES - 2 {
+				let hidden = details.len() - (ERROR_LINES - 3);
+				details.truncate(ERROR_LINES - 3);
+				details.push(format!("...and {} more.", hidden));
+			}
+
+			let mut lines = vec![format!("Could not load {}", e.path())];
+			lines.extend(details);
+			lines.push(format!("Press {} to try again.", map.key_name(Action::Restart)));
+			lines
+		},
+		None => vec![],
+	};
+
+	for (line, mut text) in &mut query.iter() {
+		let value = lines.get(line.0)
+			.cloned()
+			.unwrap_or_default();
+
+		if text.value != value {
+			text.value = value;
+		}
+	}
+}
+
 pub fn setup(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
@@ -96,6 +142,30 @@ pub fn setup(
 ) {
 	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
 
+	for i in 0..ERROR_LINES {
+		commands.spawn(TextComponents {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: Rect {
+						top: Val::Percent(10.0 + 7.0 * i as f32),
+						left: Val::Percent(5.0),
+						..Default::default()
+					},
+					..Default::default()
+				},
+				text: Text {
+					value: "".to_string(),
+					font: font_handle,
+					style: TextStyle {
+						font_size: if i == 0 { 40.0 } else { 30.0 },
+						color: Color::WHITE,
+					}
+				},
+				..Default::default()
+			})
+			.with(ErrorLine(i));
+	}
+
 	commands
 		.spawn(UiCameraComponents::default())
 		// FPS counter.
@@ -371,13 +441,25 @@ pub struct UiPlugin;
 impl Plugin for UiPlugin {
 	fn build(&self, app: &mut AppBuilder) {
 		app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+			.add_resource(menu::Menu::default())
 			.add_startup_system(setup.system())
+			.add_startup_system(menu::setup.system())
+			.add_startup_system(paths::setup.system())
+			.add_startup_system(timeline::setup.system())
+			.add_startup_system(death::setup.system())
+			.add_system(menu::menu_control.system())
+			.add_system(menu::menu_display.system())
+			.add_system(paths::paths_update.system())
+			.add_system(paths::paths_labels.system())
+			.add_system(timeline::timeline_display.system())
+			.add_system(death::death_display.system())
 			.add_system(fps_control_system.system())
 			.add_system(fps_update_system.system())
 			.add_system(turn_system.system())
 			.add_system(reruns_system.system())
 			.add_system(reruns_recolour_system.system())
 			.add_system(display_level_name.system())
+			.add_system(level_failure_system.system())
 			.add_system(ui_fade_in_out_tick_system.system())
 			.add_system(ui_fade_in_out_system.system());
 	}
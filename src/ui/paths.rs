@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttribute;
+use bevy::render::pipeline::PrimitiveTopology;
+
+use crate::mechanics::character::CharacterCommand;
+use crate::mechanics::controls::Action;
+use crate::mechanics::motion::{cell_to_world, direction_to_world};
+use crate::mechanics::{ActiveSimulation, CameraFaced, Direction, GridPosition};
+
+/// Turn numbers are drawn for at most this many upcoming steps.
+const PATH_LABELS: usize = 64;
+
+pub const GHOST_COLOURS: [Color; 4] = [
+	Color::rgba(0.3, 0.7, 1.0, 0.8),
+	Color::rgba(0.6, 1.0, 0.4, 0.8),
+	Color::rgba(1.0, 0.6, 1.0, 0.8),
+	Color::rgba(1.0, 0.8, 0.3, 0.8),
+];
+pub const BLOCKED_COLOUR: Color = Color::rgba(1.0, 0.2, 0.1, 0.9);
+
+/// One move (or wait) a ghost has yet to make this loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PathStep {
+	ghost: usize,
+	/// 1-based turn within the loop.
+	turn: usize,
+	from: GridPosition,
+	dir: Option<Direction>,
+	/// The terrain won't allow this move, so the ghost will stay put.
+	blocked: bool,
+}
+
+/// The overlay showing where each ghost is about to go.
+pub struct GhostPaths {
+	pub shown: bool,
+	steps: Vec<PathStep>,
+	arrow: Handle<Mesh>,
+	colours: Vec<Handle<StandardMaterial>>,
+	blocked: Handle<StandardMaterial>,
+}
+
+/// An arrow drawn for one step of a ghost's path.
+pub struct PathMarker;
+
+/// The turn number of the `n`th step in `GhostPaths`.
+pub struct PathLabel(usize);
+
+/// A flat arrow lying just above the ground, pointing north (+z).
+fn arrow_mesh() -> Mesh {
+	let y = -0.48;
+	let positions = vec![
+		// Shaft.
+		[-0.06, y, -0.3],
+		[0.06, y, -0.3],
+		[0.06, y, 0.05],
+		[-0.06, y, 0.05],
+		// Head.
+		[-0.18, y, 0.05],
+		[0.18, y, 0.05],
+		[0.0, y, 0.3],
+	];
+	let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+	let uvs = vec![[0.0, 0.0]; positions.len()];
+
+	Mesh {
+		primitive_topology: PrimitiveTopology::TriangleList,
+		attributes: vec![
+			VertexAttribute::position(positions),
+			VertexAttribute::normal(normals),
+			VertexAttribute::uv(uvs),
+		],
+		indices: Some(vec![0, 2, 1, 0, 3, 2, 4, 6, 5]),
+	}
+}
+
+pub fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut fonts: ResMut<Assets<Font>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
+
+	for i in 0..PATH_LABELS {
+		commands.spawn(TextComponents {
+				style: Style {
+					position_type: PositionType::Absolute,
+					..Default::default()
+				},
+				text: Text {
+					value: "".to_string(),
+					font: font_handle,
+					style: TextStyle {
+						font_size: 20.0,
+						color: Color::WHITE,
+					}
+				},
+				..Default::default()
+			})
+			.with(PathLabel(i));
+	}
+
+	let mut unshaded = |albedo| materials.add(StandardMaterial {
+		albedo,
+		shaded: false,
+		..Default::default()
+	});
+
+	let colours = GHOST_COLOURS.iter()
+		.map(|colour| unshaded(*colour))
+		.collect();
+	let blocked = unshaded(BLOCKED_COLOUR);
+
+	commands.insert_resource(GhostPaths {
+		shown: true,
+		steps: vec![],
+		arrow: meshes.add(arrow_mesh()),
+		colours,
+		blocked,
+	});
+}
+
+/// Walks each ghost's remaining commands over the terrain.
+fn upcoming_steps(sim: &ActiveSimulation) -> Vec<PathStep> {
+	let sim = match &sim.0 {
+		Some(sim) if sim.is_running() => sim,
+		_ => return vec![],
+	};
+
+	let map = sim.map();
+	let mut steps = vec![];
+
+	for (ghost, character) in sim.characters()[..sim.active_index()].iter().enumerate() {
+		if character.dead {
+			continue;
+		}
+
+		let mut pos = character.current;
+
+		for (turn, command) in character.command_list.iter().enumerate().skip(character.cmd_list_pos) {
+			let dir = match command {
+				CharacterCommand::Move(dir) => Some(*dir),
+				CharacterCommand::Wait => None,
+			};
+
+			let dest = dir.map(|dir| pos.neighbour(dir));
+			let blocked = dest.map(|dest| !map.move_allowed_by_terrain(&pos, &dest))
+				.unwrap_or(false);
+
+			steps.push(PathStep {
+				ghost,
+				turn: turn + 1,
+				from: pos,
+				dir,
+				blocked,
+			});
+
+			if let (Some(dest), false) = (dest, blocked) {
+				pos = dest;
+			}
+		}
+	}
+
+	steps
+}
+
+/// Where a step's arrow (and number) go: on the edge between the two cells.
+fn step_to_world(sim: &ActiveSimulation, step: &PathStep) -> Option<Vec3> {
+	let map = sim.0.as_ref()?.map();
+	let centre = cell_to_world(map, step.from)?;
+
+	Some(match step.dir {
+		Some(dir) => centre + direction_to_world(dir) * 0.5,
+		None => centre,
+	})
+}
+
+pub fn paths_update(
+	mut commands: Commands,
+	actions: Res<Input<Action>>,
+	sim: Res<ActiveSimulation>,
+	mut paths: ResMut<GhostPaths>,
+	mut markers: Query<(Entity, &PathMarker)>,
+) {
+	if actions.just_pressed(Action::TogglePaths) {
+		paths.shown = !paths.shown;
+	}
+
+	let steps = if paths.shown {
+		upcoming_steps(&sim)
+	} else {
+		vec![]
+	};
+
+	if steps == paths.steps {
+		return;
+	}
+
+	for (ent, _marker) in &mut markers.iter() {
+		commands.despawn(ent);
+	}
+
+	for step in steps.iter() {
+		let dir = match step.dir {
+			Some(dir) => dir,
+			None => continue,
+		};
+
+		let translation = match step_to_world(&sim, step) {
+			Some(translation) => translation,
+			None => continue,
+		};
+
+		let facing = direction_to_world(dir);
+		let rotation = Quat::from_rotation_y(facing.x().atan2(facing.z()));
+
+		let material = if step.blocked {
+			paths.blocked
+		} else {
+			paths.colours[step.ghost % paths.colours.len()]
+		};
+
+		commands.spawn(PbrComponents {
+				mesh: paths.arrow,
+				material,
+				transform: Transform::from_translation_rotation(translation, rotation),
+				draw: Draw {
+					is_transparent: true,
+					..Default::default()
+				},
+				..Default::default()
+			})
+			.with(PathMarker);
+	}
+
+	paths.steps = steps;
+}
+
+/// Pins each turn number over its step, as seen from the camera.
+pub fn paths_labels(
+	paths: Res<GhostPaths>,
+	sim: Res<ActiveSimulation>,
+	windows: Res<Windows>,
+	mut cameras: Query<(&CameraFaced, &Transform)>,
+	mut labels: Query<(&PathLabel, &mut Text, &mut Style)>,
+) {
+	let window = match windows.get_primary() {
+		Some(window) => window,
+		None => return,
+	};
+
+	let mut view = None;
+	for (_tag, tx) in &mut cameras.iter() {
+		view = Some(tx.value().inverse());
+	}
+
+	for (label, mut text, mut style) in &mut labels.iter() {
+		let step = paths.steps.get(label.0);
+		let world = step.and_then(|step| step_to_world(&sim, step));
+
+		let (step, world, view) = match (step, world, view) {
+			(Some(step), Some(world), Some(view)) => (step, world, view),
+			_ => {
+				if !text.value.is_empty() {
+					text.value = String::new();
+				}
+				continue;
+			},
+		};
+
+		// The camera is orthographic, and its view is already in pixels from the centre of the window.
+		let on_screen = view.transform_point3(world);
+
+		// Nudge each ghost's numbers apart, in case their paths cross.
+		let nudge = 10.0 * step.ghost as f32;
+
+		style.position.left = Val::Px(window.width as f32 * 0.5 + on_screen.x() - 5.0 + nudge);
+		style.position.top = Val::Px(window.height as f32 * 0.5 - on_screen.y() - 10.0);
+
+		let value = step.turn.to_string();
+		if text.value != value {
+			text.value = value;
+		}
+		text.style.color = if step.blocked {
+			BLOCKED_COLOUR
+		} else {
+			GHOST_COLOURS[step.ghost % GHOST_COLOURS.len()]
+		};
+	}
+}
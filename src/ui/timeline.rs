@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+use crate::mechanics::character::CharacterCommand;
+use crate::mechanics::{ActiveSimulation, Direction};
+
+use super::menu::Menu;
+use super::paths::{BLOCKED_COLOUR, GHOST_COLOURS};
+
+/// Only the most recent loops fit on screen...
+const TIMELINE_ROWS: usize = 8;
+/// ...and only this many turns of each.
+const TIMELINE_COLS: usize = 24;
+
+const CELL_WIDTH: f32 = 16.0;
+const CELL_HEIGHT: f32 = 20.0;
+
+const CURRENT_TURN_COLOUR: Color = Color::rgb(1.0, 0.8, 0.2);
+const UPCOMING_COLOUR: Color = Color::rgba(0.7, 0.7, 0.7, 0.6);
+
+/// One cell of the timeline in the top right: a loop's command on one turn.
+#[derive(Debug, Default)]
+pub struct TimelineCell {
+	row: usize,
+	col: usize,
+}
+
+pub fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut fonts: ResMut<Assets<Font>>,
+) {
+	let font_handle = asset_server.load_sync(&mut fonts, "assets/fonts/as/AlegreyaSans-Bold.ttf").unwrap();
+
+	for row in 0..TIMELINE_ROWS {
+		for col in 0..TIMELINE_COLS {
+			commands.spawn(TextComponents {
+					style: Style {
+						position_type: PositionType::Absolute,
+						position: Rect {
+							top: Val::Px(5.0 + CELL_HEIGHT * row as f32),
+							right: Val::Px(5.0 + CELL_WIDTH * (TIMELINE_COLS - 1 - col) as f32),
+							..Default::default()
+						},
+						..Default::default()
+					},
+					text: Text {
+						value: "".to_string(),
+						font: font_handle,
+						style: TextStyle {
+							font_size: 20.0,
+							color: Color::WHITE,
+						}
+					},
+					..Default::default()
+				})
+				.with(TimelineCell { row, col });
+		}
+	}
+}
+
+fn icon(command: CharacterCommand) -> &'static str {
+	match command {
+		CharacterCommand::Move(Direction::North) => "^",
+		CharacterCommand::Move(Direction::East) => ">",
+		CharacterCommand::Move(Direction::South) => "v",
+		CharacterCommand::Move(Direction::West) => "<",
+		CharacterCommand::Wait => "-",
+	}
+}
+
+pub fn timeline_display(
+	sim: Res<ActiveSimulation>,
+	menu: Res<Menu>,
+	mut query: Query<(&TimelineCell, &mut Text)>,
+) {
+	let sim = match &sim.0 {
+		Some(sim) if !menu.open => sim,
+		_ => {
+			for (_cell, mut text) in &mut query.iter() {
+				if !text.value.is_empty() {
+					text.value = String::new();
+				}
+			}
+			return;
+		},
+	};
+
+	let state = sim.state();
+	let limit = sim.turn_limit();
+	let current = state.turn.turn;
+	let characters = sim.characters();
+	let active = sim.active_index();
+
+	// Keep the newest loops, and scroll long loops so the current turn is in view.
+	let first_row = characters.len().saturating_sub(TIMELINE_ROWS);
+	let first_col = current.saturating_sub(TIMELINE_COLS / 2)
+		.min(limit.saturating_sub(TIMELINE_COLS));
+
+	for (cell, mut text) in &mut query.iter() {
+		let index = first_row + cell.row;
+		let turn = first_col + cell.col;
+
+		let (value, colour) = match characters.get(index) {
+			Some(character) if turn < limit => {
+				// Ghosts know their whole loop; anything past `cmd_list_pos` hasn't happened yet.
+				let taken = if index == active {
+					turn < character.command_list.len()
+				} else {
+					turn < character.cmd_list_pos
+				};
+
+				let value = character.command_list.get(turn)
+					.map(|command| icon(*command))
+					.unwrap_or(".");
+
+				let colour = if state.was_blocked(index, turn) {
+					BLOCKED_COLOUR
+				} else if turn == current {
+					CURRENT_TURN_COLOUR
+				} else if !taken {
+					UPCOMING_COLOUR
+				} else if index == active {
+					Color::WHITE
+				} else {
+					GHOST_COLOURS[index % GHOST_COLOURS.len()]
+				};
+
+				(value, colour)
+			},
+			_ => ("", Color::WHITE),
+		};
+
+		if text.value != value {
+			text.value = value.to_string();
+		}
+		text.style.color = colour;
+	}
+}